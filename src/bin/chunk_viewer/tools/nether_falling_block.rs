@@ -8,6 +8,7 @@ use mc_utils::flood_fill::{flood_fill, spider};
 use mc_utils::world::{Dimension, World};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use mc_utils::block_ids;
+use mc_utils::chunk::ChunkFilter;
 use mc_utils::litematica::{LitematicaBuilder, LitematicaRegionBuilder};
 use std::fs::File;
 use rayon::iter::{ParallelBridge, ParallelIterator};
@@ -89,8 +90,16 @@ impl NetherFallingBlockTool {
 
             let result = world.region_pos_iter(Dimension::Nether)?.par_bridge().flat_map(|region_pos| -> anyhow::Result<Vec<ChunkPos>> {
                 let mut region_fireless_chunks: Vec<ChunkPos> = Vec::new();
-                let region = world.get_region_uncached(region_pos, Dimension::Nether)?.unwrap();
+                // Only the block ids are needed to look for fire
+                let mut region = world.get_region_uncached(region_pos, Dimension::Nether, ChunkFilter::BLOCK_IDS)?.unwrap();
                 for (chunk_pos, chunk) in region.chunk_iter() {
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(err) => {
+                            println!("Skipping chunk in region ({}, {}): {}", region_pos.x, region_pos.z, err);
+                            continue;
+                        }
+                    };
                     let mut has_fire = false;
                     for (_, block) in chunk.block_iter() {
                         if block.block_id == block_ids::FIRE {
@@ -99,7 +108,7 @@ impl NetherFallingBlockTool {
                         }
                     }
                     if !has_fire {
-                        region_fireless_chunks.push(chunk_pos.offset(region_pos.into()))
+                        region_fireless_chunks.push(chunk_pos)
                     }
                 }
 
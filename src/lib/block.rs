@@ -1,14 +1,80 @@
-#[derive(Copy, Clone)]
+use std::collections::BTreeMap;
+use crate::nbt;
+use crate::nbt::{NbtCompound, NbtTag};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Block {
-    pub block_id: u8,
+    /// The full 12 bit id, including the bits from the `Add` array
+    pub block_id: u16,
     pub data: u8
 }
 
 impl Block {
-    pub fn new(block_id: u8, data: u8) -> Block {
+    pub const AIR: Block = Block {
+        block_id: 0,
+        data: 0
+    };
+
+    pub fn new(block_id: u16, data: u8) -> Block {
         Block {
             block_id,
             data
         }
     }
+}
+
+/// A namespaced block state from a 1.13+ section palette, e.g. `minecraft:lever` with `face=wall`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockState {
+    pub name: String,
+    pub properties: BTreeMap<String, String>
+}
+
+impl BlockState {
+    pub fn new<S: Into<String>>(name: S) -> BlockState {
+        BlockState {
+            name: name.into(),
+            properties: BTreeMap::new()
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    pub(crate) fn from_nbt(nbt: &NbtCompound) -> nbt::Result<BlockState> {
+        let Some(NbtTag::String(name)) = nbt.get("Name") else {
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure, palette entry Name missing or not a string"));
+        };
+        let mut properties = BTreeMap::new();
+        if let Some(tag) = nbt.get("Properties") {
+            let NbtTag::Compound(tag) = tag else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, palette entry Properties not a compound"));
+            };
+            for (property, val) in tag.iter() {
+                let NbtTag::String(val) = val else {
+                    return Err(nbt::NbtError::custom("Unexpected Chunk Structure, block state property not a string"));
+                };
+                properties.insert(property.as_str_lossy().into_owned(), val.as_str_lossy().into_owned());
+            }
+        }
+
+        Ok(BlockState {
+            name: name.as_str_lossy().into_owned(),
+            properties
+        })
+    }
+
+    pub(crate) fn to_nbt(&self) -> NbtCompound {
+        let mut nbt = NbtCompound::new();
+        nbt.insert("Name", self.name.as_str());
+        if !self.properties.is_empty() {
+            let mut properties = NbtCompound::new();
+            for (property, val) in &self.properties {
+                properties.insert(property.as_str(), val.as_str());
+            }
+            nbt.insert("Properties", properties);
+        }
+        nbt
+    }
 }
\ No newline at end of file
@@ -1 +1 @@
-pub const FIRE: u8 = 51;
\ No newline at end of file
+pub const FIRE: u16 = 51;
\ No newline at end of file
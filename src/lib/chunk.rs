@@ -1,70 +1,794 @@
-use std::io::{Error, Read};
-use byteorder::{BigEndian, ReadBytesExt};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{Read, Write};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::Compression;
 use flate2::read::{GzDecoder, ZlibDecoder};
-use java_string::JavaString;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use thiserror::Error;
 use crate::positions::{BlockPos, ChunkPos};
-use crate::block::Block;
+use crate::block::{Block, BlockState};
 use crate::nbt;
-use crate::nbt::{LeafTag, NbtPath, NbtPathElement, NbtVisitor, visit_nbt};
+use crate::nbt::{LeafTag, NbtCompound, NbtList, NbtPath, NbtPathElement, NbtTag, NbtTreeVisitor, NbtVisitor, NbtWriter, TagId, Visit, visit_nbt};
 
 pub struct Chunk {
     pub length: u32,
     pub compression_type: u8,
+    /// The world time this chunk was last saved at
+    pub last_update: i64,
+    /// Total ticks players have spent nearby, used for local difficulty
+    pub inhabited_time: i64,
+    /// Whether ores, trees, structures etc. have been generated in this chunk
+    pub terrain_populated: bool,
+    pub light_populated: bool,
+    /// The chunk format version `V`, missing from very old chunks
+    pub version: Option<i8>,
     data: ChunkData
 }
 
 #[derive(Debug)]
 struct ChunkData {
     pos: ChunkPos,
-    sections: Vec<ChunkSection>,
-    entities: Vec<Entity>
+    /// Keyed by each section's `Y`, empty sections are often left out entirely
+    sections: BTreeMap<i8, ChunkSection>,
+    entities: Vec<Entity>,
+    tile_entities: Vec<TileEntity>,
+    tile_ticks: Vec<ScheduledTick>,
+    /// The lowest y where sky light reaches full strength, by `z << 4 | x`
+    height_map: Vec<i32>,
+    /// Biome ids by `z << 4 | x`, a byte array before 1.13 and an int array after
+    biomes: Vec<i32>,
+    biomes_as_ints: bool,
+    /// Tags outside of `Level`, e.g. `DataVersion`
+    extra: NbtCompound,
+    /// Tags inside `Level` that aren't modeled
+    level_extra: NbtCompound,
+    /// Which of the modeled tags `Level` had
+    level_types: TagTypes,
+    /// The element types of the modeled lists in `Level`, vanilla saves empty ones as lists of
+    /// `TAG_End`
+    level_list_types: TagTypes,
+    /// What was parsed, writing a chunk that's missing parts would drop them from the world
+    filter: ChunkFilter
 }
-#[derive(Debug)]
+impl ChunkData {
+    /// The element type to write one of `Level`'s compound lists with. Empty ones keep the type they
+    /// were read with, or are lists of `TAG_End` like vanilla saves them.
+    fn list_type(&self, field: &str, is_empty: bool) -> TagId {
+        if is_empty {
+            self.level_list_types.get(field).unwrap_or(TagId::End)
+        } else {
+            TagId::Compound
+        }
+    }
+}
+#[derive(Debug, Default)]
 struct ChunkSection {
+    y: Option<i8>,
     blocks: Vec<i8>,
-    block_data: Vec<i8>
+    /// The upper 4 bits of block ids above 255, only present if the section has any
+    add: Vec<i8>,
+    block_data: Vec<i8>,
+    block_light: Vec<i8>,
+    sky_light: Vec<i8>,
+    /// 1.13+ sections have these instead of `Blocks`, `Add` and `Data`
+    palette: Vec<BlockState>,
+    block_states: Vec<i64>,
+    extra: NbtCompound
 }
+impl ChunkSection {
+    /// An all air section, with full sky light since that's what the game assumes for missing
+    /// sections above the height map
+    fn empty(y: i8) -> ChunkSection {
+        ChunkSection {
+            y: Some(y),
+            blocks: vec![0; 4096],
+            add: Vec::new(),
+            block_data: vec![0; 2048],
+            block_light: vec![0; 2048],
+            sky_light: vec![-1; 2048],
+            palette: Vec::new(),
+            block_states: Vec::new(),
+            extra: NbtCompound::new()
+        }
+    }
 
-#[derive(Debug)]
+    #[inline]
+    fn block(&self, index: usize) -> Block {
+        // Any of the arrays could be missing, or have been filtered out
+        let block_id = self.blocks.get(index).map_or(0, |b| *b as u8) as u16;
+        let add = nibble(&self.add, index) as u16;
+        Block::new(add << 8 | block_id, nibble(&self.block_data, index))
+    }
+
+    fn block_state(&self, index: usize) -> Option<&BlockState> {
+        if self.palette.is_empty() {
+            return None;
+        }
+        // At least 4 bits per entry, more once the palette doesn't fit
+        let bits = (usize::BITS - (self.palette.len() - 1).leading_zeros()).max(4) as usize;
+        self.palette.get(packed_value(&self.block_states, bits, index))
+    }
+
+    fn set_block(&mut self, index: usize, block: Block) {
+        if self.blocks.len() < 4096 {
+            self.blocks.resize(4096, 0);
+        }
+        self.blocks[index] = block.block_id as u8 as i8;
+        // Add is left out entirely while every id fits in a byte
+        let add = (block.block_id >> 8) as u8 & 0xf;
+        if add != 0 || !self.add.is_empty() {
+            set_nibble(&mut self.add, index, add);
+        }
+        set_nibble(&mut self.block_data, index, block.data);
+    }
+
+    fn write<W: Write>(&self, writer: &mut NbtWriter<W>) -> nbt::Result<()> {
+        writer.begin_compound_element()?;
+        if let Some(y) = self.y {
+            writer.write_leaf("Y", &LeafTag::Byte(y))?;
+        }
+        for (name, array) in [("Blocks", &self.blocks), ("Add", &self.add), ("Data", &self.block_data),
+                              ("BlockLight", &self.block_light), ("SkyLight", &self.sky_light)] {
+            if !array.is_empty() {
+                writer.write_leaf(name, &LeafTag::ByteArray(array.clone()))?;
+            }
+        }
+        if !self.palette.is_empty() {
+            let palette = compound_list_tag(self.palette.iter().map(BlockState::to_nbt));
+            NbtTag::List(palette).write_named(writer, "Palette")?;
+        }
+        if !self.block_states.is_empty() {
+            writer.write_leaf("BlockStates", &LeafTag::LongArray(self.block_states.clone()))?;
+        }
+        self.extra.write_entries(writer)?;
+        writer.end_compound()
+    }
+}
+
+/// Reads a value out of a packed 4 bit array, low nibble first
+#[inline]
+fn nibble(array: &[i8], index: usize) -> u8 {
+    let byte = array.get(index >> 1).map_or(0, |b| *b as u8);
+    if index & 1 == 0 {
+        byte & 0xf
+    } else {
+        byte >> 4
+    }
+}
+
+/// Reads entry `index` out of a `BlockStates` array with `bits` per entry. Up to 1.15 entries are
+/// packed end to end and can be split across two longs, since 1.16 the leftover high bits of each
+/// long are padding instead. The two only differ when `bits` doesn't divide 64, in which case the
+/// padded layout needs more longs, so the length tells them apart.
+fn packed_value(states: &[i64], bits: usize, index: usize) -> usize {
+    let mask = (1u64 << bits) - 1;
+    let word = |index: usize| states.get(index).map_or(0, |val| *val as u64);
+    if states.len() == 4096 * bits / 64 {
+        let bit = index * bits;
+        let offset = bit % 64;
+        let mut val = word(bit / 64) >> offset;
+        if offset + bits > 64 {
+            val |= word(bit / 64 + 1) << (64 - offset);
+        }
+        (val & mask) as usize
+    } else {
+        let per_word = 64 / bits;
+        ((word(index / per_word) >> (index % per_word * bits)) & mask) as usize
+    }
+}
+
+fn set_nibble(array: &mut Vec<i8>, index: usize, value: u8) {
+    if array.len() < 2048 {
+        array.resize(2048, 0);
+    }
+    let byte = array[index >> 1] as u8;
+    array[index >> 1] = if index & 1 == 0 {
+        byte & 0xf0 | value & 0xf
+    } else {
+        byte & 0x0f | value << 4
+    } as i8;
+}
+
+/// A block entity, e.g. a chest, hopper, spawner or sign
+#[derive(Debug, Clone)]
+pub struct TileEntity {
+    pub id: String,
+    pub pos: BlockPos,
+    /// Everything other than the id and position, e.g. `Items` for containers
+    pub nbt: NbtCompound
+}
+impl TileEntity {
+    fn from_nbt(mut nbt: NbtCompound) -> nbt::Result<TileEntity> {
+        let Some(NbtTag::String(id)) = nbt.remove("id") else {
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure, tile entity id missing or not a string"));
+        };
+        let mut coord = |name: &str| match nbt.remove(name) {
+            Some(NbtTag::Int(val)) => Ok(val),
+            _ => Err(nbt::NbtError::custom(format!("Unexpected Chunk Structure, tile entity {name} missing or not an int")))
+        };
+        let pos = BlockPos::new(coord("x")?, coord("y")?, coord("z")?);
+
+        Ok(TileEntity {
+            id: id.into_string()?,
+            pos,
+            nbt
+        })
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let mut nbt = NbtCompound::new();
+        nbt.insert("id", self.id.as_str());
+        nbt.insert("x", self.pos.x);
+        nbt.insert("y", self.pos.y);
+        nbt.insert("z", self.pos.z);
+        extend(&mut nbt, &self.nbt);
+        nbt
+    }
+}
+
+/// An entity, with the commonly needed fields pulled out of its NBT. Which of the optional ones are
+/// present depends on the kind of entity (and the version that saved it).
+#[derive(Debug, Clone)]
 pub struct Entity {
+    pub id: String,
     pub pos: (f64, f64, f64),
+    pub motion: (f64, f64, f64),
+    /// Yaw and pitch, in degrees
+    pub rotation: (f32, f32),
+    /// From `UUIDMost`/`UUIDLeast`, or the int array `UUID` in newer versions
+    pub uuid: Option<u128>,
+    /// `Age` of items and xp orbs, or the breeding age of mobs
+    pub age: Option<i32>,
+    /// `Time` of falling blocks, the number of ticks they've existed for
+    pub time: Option<i32>,
+    /// The block id and data value of falling blocks
+    pub block: Option<String>,
+    pub data: Option<u8>,
+    pub dimension: Option<i32>,
+    /// The item of item entities and item frames
+    pub item: Option<ItemStack>,
+    /// The entity being ridden, only in pre 1.9 saves where riding is stored on the rider
+    pub riding: Option<Box<Entity>>,
+    pub passengers: Vec<Entity>,
+    /// Everything that isn't one of the fields above
+    pub nbt: NbtCompound,
+    types: TagTypes
+}
+const ENTITY_FIELDS: [&str; 15] = ["id", "Pos", "Motion", "Rotation", "UUIDMost", "UUIDLeast", "UUID", "Age", "Time",
+    "Block", "Data", "Dimension", "Item", "Riding", "Passengers"];
+impl Entity {
+    pub fn new<S: Into<String>>(id: S) -> Entity {
+        Entity {
+            id: id.into(),
+            pos: (0.0, 0.0, 0.0),
+            motion: (0.0, 0.0, 0.0),
+            rotation: (0.0, 0.0),
+            uuid: None,
+            age: None,
+            time: None,
+            block: None,
+            data: None,
+            dimension: None,
+            item: None,
+            riding: None,
+            passengers: Vec::new(),
+            nbt: NbtCompound::new(),
+            types: TagTypes::default()
+        }
+    }
+
+    fn from_nbt(mut nbt: NbtCompound) -> nbt::Result<Entity> {
+        let types = TagTypes::of(&nbt, &ENTITY_FIELDS);
+        let Some(id) = take(&mut nbt, "id", string_value)? else {
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure, entity has no id"));
+        };
+        let pos = take(&mut nbt, "Pos", double_list)?.unwrap_or_default();
+        let motion = take(&mut nbt, "Motion", double_list)?.unwrap_or_default();
+        let rotation = take(&mut nbt, "Rotation", float_pair)?.unwrap_or_default();
+
+        // Anything other than a complete pair, or the int array on its own, is left as it was
+        let uuid = if nbt.contains_key("UUIDMost") && nbt.contains_key("UUIDLeast") {
+            let most = take(&mut nbt, "UUIDMost", NbtTag::as_long)?.unwrap_or_default();
+            let least = take(&mut nbt, "UUIDLeast", NbtTag::as_long)?.unwrap_or_default();
+            Some((most as u64 as u128) << 64 | least as u64 as u128)
+        } else if !nbt.contains_key("UUIDMost") && !nbt.contains_key("UUIDLeast") {
+            take(&mut nbt, "UUID", int_array_uuid)?
+        } else {
+            None
+        };
+
+        let riding = take(&mut nbt, "Riding", compound_value)?
+            .map(|riding| Entity::from_nbt(riding).map(Box::new))
+            .transpose()?;
+        let passengers = take(&mut nbt, "Passengers", compound_list)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(Entity::from_nbt)
+            .collect::<nbt::Result<_>>()?;
+        let item = take(&mut nbt, "Item", compound_value)?
+            .map(ItemStack::from_nbt)
+            .transpose()?;
+
+        Ok(Entity {
+            id,
+            pos,
+            motion,
+            rotation,
+            uuid,
+            age: take(&mut nbt, "Age", int_value)?,
+            time: take(&mut nbt, "Time", int_value)?,
+            block: take(&mut nbt, "Block", string_value)?,
+            data: take(&mut nbt, "Data", int_value)?.map(|data| data as u8),
+            dimension: take(&mut nbt, "Dimension", int_value)?,
+            item,
+            riding,
+            passengers,
+            nbt,
+            types
+        })
+    }
+
+    /// The fields are written back with the types they were read with. Entities made with
+    /// [`Entity::new`] get the types 1.12 uses.
+    fn to_nbt(&self) -> NbtCompound {
+        let types = &self.types;
+        let mut nbt = NbtCompound::new();
+        nbt.insert("id", self.id.as_str());
+        if types.keep("Pos", self.pos == (0.0, 0.0, 0.0)) {
+            nbt.insert("Pos", double_list_tag(self.pos));
+        }
+        if types.keep("Motion", self.motion == (0.0, 0.0, 0.0)) {
+            nbt.insert("Motion", double_list_tag(self.motion));
+        }
+        if types.keep("Rotation", self.rotation == (0.0, 0.0)) {
+            let mut rotation = NbtList::with_type(TagId::Float);
+            rotation.push(self.rotation.0).unwrap();
+            rotation.push(self.rotation.1).unwrap();
+            nbt.insert("Rotation", rotation);
+        }
+        if let Some(uuid) = self.uuid {
+            // 1.16 replaced the pair of longs with an int array
+            if types.get("UUID") == Some(TagId::IntArray) && types.get("UUIDMost").is_none() {
+                nbt.insert("UUID", vec![(uuid >> 96) as i32, (uuid >> 64) as i32, (uuid >> 32) as i32, uuid as i32]);
+            } else {
+                nbt.insert("UUIDMost", (uuid >> 64) as i64);
+                nbt.insert("UUIDLeast", uuid as i64);
+            }
+        }
+        if let Some(age) = self.age {
+            // Items and xp orbs only count up to a few thousand ticks, mobs use the full int range
+            let default = match self.id.as_str() {
+                "Item" | "XPOrb" | "minecraft:item" | "minecraft:xp_orb" => TagId::Short,
+                _ => TagId::Int
+            };
+            nbt.insert("Age", int_tag(age, types.get("Age").unwrap_or(default)));
+        }
+        if let Some(time) = self.time {
+            nbt.insert("Time", int_tag(time, types.get("Time").unwrap_or(TagId::Int)));
+        }
+        if let Some(block) = &self.block {
+            nbt.insert("Block", block.as_str());
+        }
+        if let Some(data) = self.data {
+            nbt.insert("Data", int_tag(data as i32, types.get("Data").unwrap_or(TagId::Byte)));
+        }
+        if let Some(dimension) = self.dimension {
+            nbt.insert("Dimension", int_tag(dimension, types.get("Dimension").unwrap_or(TagId::Int)));
+        }
+        if let Some(item) = &self.item {
+            nbt.insert("Item", item.to_nbt());
+        }
+        if let Some(riding) = &self.riding {
+            nbt.insert("Riding", riding.to_nbt());
+        }
+        if types.keep("Passengers", self.passengers.is_empty()) {
+            nbt.insert("Passengers", compound_list_tag(self.passengers.iter().map(Entity::to_nbt)));
+        }
+        extend(&mut nbt, &self.nbt);
+        nbt
+    }
+}
+
+/// A pending block update from `TileTicks`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledTick {
+    /// Namespaced id of the block the tick was scheduled for, or the numeric id as a string in very
+    /// old saves
+    pub block: String,
+    pub pos: BlockPos,
+    /// Ticks until the update is due, counted from when the chunk was saved
+    pub delay: i32,
+    /// Among ticks due on the same game tick, lower priorities run first
+    pub priority: i32,
+    /// Any other tags the tick had
+    pub nbt: NbtCompound,
+    types: TagTypes
+}
+const TICK_FIELDS: [&str; 6] = ["i", "x", "y", "z", "t", "p"];
+impl ScheduledTick {
+    pub fn new<S: Into<String>>(block: S, pos: BlockPos, delay: i32, priority: i32) -> ScheduledTick {
+        ScheduledTick {
+            block: block.into(),
+            pos,
+            delay,
+            priority,
+            nbt: NbtCompound::new(),
+            types: TagTypes::default()
+        }
+    }
+
+    fn from_nbt(mut nbt: NbtCompound) -> nbt::Result<ScheduledTick> {
+        let types = TagTypes::of(&nbt, &TICK_FIELDS);
+        let block = match nbt.remove("i") {
+            Some(NbtTag::String(block)) => block.into_string()?,
+            Some(NbtTag::Int(block)) => block.to_string(),
+            _ => return Err(nbt::NbtError::custom("Unexpected Chunk Structure, tile tick i missing or not a string"))
+        };
+        let mut int = |name: &str| match take(&mut nbt, name, NbtTag::as_int)? {
+            Some(val) => Ok(val),
+            None => Err(nbt::NbtError::custom(format!("Unexpected Chunk Structure, tile tick {name} missing")))
+        };
+        let pos = BlockPos::new(int("x")?, int("y")?, int("z")?);
+        let delay = int("t")?;
+
+        Ok(ScheduledTick {
+            block,
+            pos,
+            delay,
+            // Priorities were only added in 1.8
+            priority: take(&mut nbt, "p", NbtTag::as_int)?.unwrap_or(0),
+            nbt,
+            types
+        })
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let mut nbt = NbtCompound::new();
+        match self.block.parse::<i32>() {
+            Ok(block) if self.types.get("i") != Some(TagId::String) => nbt.insert("i", block),
+            _ => nbt.insert("i", self.block.as_str())
+        };
+        if self.types.keep("p", self.priority == 0) {
+            nbt.insert("p", self.priority);
+        }
+        nbt.insert("t", self.delay);
+        nbt.insert("x", self.pos.x);
+        nbt.insert("y", self.pos.y);
+        nbt.insert("z", self.pos.z);
+        extend(&mut nbt, &self.nbt);
+        nbt
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemStack {
+    /// Namespaced id, or the numeric id as a string in very old saves
     pub id: String,
-    pub block: Option<String>
+    pub count: i8,
+    pub damage: i16,
+    pub tag: Option<NbtCompound>,
+    /// Anything else, e.g. `Slot`
+    pub nbt: NbtCompound,
+    types: TagTypes
+}
+const ITEM_FIELDS: [&str; 4] = ["id", "Count", "Damage", "tag"];
+impl ItemStack {
+    pub fn new<S: Into<String>>(id: S, count: i8) -> ItemStack {
+        ItemStack {
+            id: id.into(),
+            count,
+            damage: 0,
+            tag: None,
+            nbt: NbtCompound::new(),
+            types: TagTypes::default()
+        }
+    }
+
+    fn from_nbt(mut nbt: NbtCompound) -> nbt::Result<ItemStack> {
+        let types = TagTypes::of(&nbt, &ITEM_FIELDS);
+        let id = match nbt.remove("id") {
+            Some(NbtTag::String(id)) => id.into_string()?,
+            Some(NbtTag::Short(id)) => id.to_string(),
+            _ => return Err(nbt::NbtError::custom("Unexpected Chunk Structure, item id missing or not a string"))
+        };
+
+        Ok(ItemStack {
+            id,
+            count: take(&mut nbt, "Count", NbtTag::as_byte)?.unwrap_or(1),
+            damage: take(&mut nbt, "Damage", NbtTag::as_short)?.unwrap_or(0),
+            tag: take(&mut nbt, "tag", compound_value)?,
+            nbt,
+            types
+        })
+    }
+
+    fn to_nbt(&self) -> NbtCompound {
+        let mut nbt = NbtCompound::new();
+        match self.id.parse::<i16>() {
+            Ok(id) if self.types.get("id") != Some(TagId::String) => nbt.insert("id", id),
+            _ => nbt.insert("id", self.id.as_str())
+        };
+        if self.types.keep("Count", self.count == 1) {
+            nbt.insert("Count", self.count);
+        }
+        // Moved into the item's tag in 1.13
+        if self.types.keep("Damage", self.damage == 0) {
+            nbt.insert("Damage", self.damage);
+        }
+        if let Some(tag) = &self.tag {
+            nbt.insert("tag", tag.clone());
+        }
+        extend(&mut nbt, &self.nbt);
+        nbt
+    }
+}
+
+/// Which of the modeled tags a compound had when it was read, and their types, so writing it back
+/// doesn't add tags or change types. `None` for values made from scratch, which get everything
+/// 1.12 would save.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct TagTypes(Option<Vec<(&'static str, TagId)>>);
+impl TagTypes {
+    fn of(nbt: &NbtCompound, fields: &[&'static str]) -> TagTypes {
+        TagTypes(Some(fields.iter().filter_map(|&field| Some((field, nbt.get(field)?.tag_id()))).collect()))
+    }
+
+    fn insert(&mut self, field: &'static str, tag_id: TagId) {
+        let types = self.0.get_or_insert_with(Vec::new);
+        if !types.iter().any(|(name, _)| *name == field) {
+            types.push((field, tag_id));
+        }
+    }
+
+    fn get(&self, field: &str) -> Option<TagId> {
+        self.0.as_ref()?.iter().find(|(name, _)| *name == field).map(|(_, tag_id)| *tag_id)
+    }
+
+    /// Whether a field that has a default gets written: if it was there when read, or has been
+    /// changed from the default since
+    fn keep(&self, field: &str, is_default: bool) -> bool {
+        self.0.is_none() || !is_default || self.get(field).is_some()
+    }
+}
+
+/// An integer in the type it was read as
+fn int_tag(val: i32, tag_id: TagId) -> NbtTag {
+    match tag_id {
+        TagId::Byte => NbtTag::Byte(val as i8),
+        TagId::Short => NbtTag::Short(val as i16),
+        _ => NbtTag::Int(val)
+    }
+}
+
+/// Removes `name` from `nbt` and converts it, erroring if it's there but `convert` doesn't accept it
+fn take<T, F: FnOnce(&NbtTag) -> Option<T>>(nbt: &mut NbtCompound, name: &str, convert: F) -> nbt::Result<Option<T>> {
+    let Some(tag) = nbt.remove(name) else {
+        return Ok(None);
+    };
+    match convert(&tag) {
+        Some(val) => Ok(Some(val)),
+        None => Err(nbt::NbtError::custom(format!("Unexpected Chunk Structure, {name} has the wrong type ({:?})", tag.tag_id())))
+    }
+}
+
+/// Any integer type that fits in an i32, since the type of some fields changed between versions
+fn int_value(tag: &NbtTag) -> Option<i32> {
+    match tag {
+        NbtTag::Byte(val) => Some(*val as i32),
+        NbtTag::Short(val) => Some(*val as i32),
+        NbtTag::Int(val) => Some(*val),
+        _ => None
+    }
+}
+
+fn string_value(tag: &NbtTag) -> Option<String> {
+    tag.as_string().map(|val| val.as_str_lossy().into_owned())
+}
+
+fn compound_value(tag: &NbtTag) -> Option<NbtCompound> {
+    tag.as_compound().cloned()
 }
 
+fn compound_list(tag: &NbtTag) -> Option<Vec<NbtCompound>> {
+    tag.as_list()?.iter().map(|tag| tag.as_compound().cloned()).collect()
+}
+
+fn double_list(tag: &NbtTag) -> Option<(f64, f64, f64)> {
+    let list = tag.as_list()?;
+    if list.len() != 3 {
+        return None;
+    }
+    Some((list[0].as_double()?, list[1].as_double()?, list[2].as_double()?))
+}
+
+fn float_pair(tag: &NbtTag) -> Option<(f32, f32)> {
+    let list = tag.as_list()?;
+    if list.len() != 2 {
+        return None;
+    }
+    Some((list[0].as_float()?, list[1].as_float()?))
+}
+
+fn int_array_uuid(tag: &NbtTag) -> Option<u128> {
+    let &[a, b, c, d] = tag.as_int_array()? else {
+        return None;
+    };
+    Some([a, b, c, d].iter().fold(0, |uuid, part| uuid << 32 | *part as u32 as u128))
+}
+
+/// Adds every entry of `other` to `nbt`
+fn extend(nbt: &mut NbtCompound, other: &NbtCompound) {
+    for (name, tag) in other.iter() {
+        nbt.insert(name, tag.clone());
+    }
+}
+
+fn double_list_tag((x, y, z): (f64, f64, f64)) -> NbtList {
+    let mut list = NbtList::with_type(TagId::Double);
+    for val in [x, y, z] {
+        list.push(val).unwrap();
+    }
+    list
+}
+
+fn compound_list_tag<I: IntoIterator<Item=NbtCompound>>(compounds: I) -> NbtList {
+    let mut list = NbtList::with_type(TagId::Compound);
+    for compound in compounds {
+        list.push(compound).unwrap();
+    }
+    list
+}
+
+/// Selects which parts of a chunk get decoded when parsing. Anything not selected is skipped over
+/// in the NBT stream without being allocated, which matters a lot for whole world scans.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkFilter {
+    pub blocks: bool,
+    pub block_data: bool,
+    pub entities: bool,
+    pub tile_entities: bool,
+    pub tile_ticks: bool,
+    /// `HeightMap` and `Biomes`
+    pub height_map_biomes: bool,
+    pub light: bool,
+    /// Every tag none of the other fields cover, kept so that [`Chunk::write`] doesn't drop them
+    pub unknown: bool
+}
+impl ChunkFilter {
+    pub const ALL: ChunkFilter = ChunkFilter {
+        blocks: true,
+        block_data: true,
+        entities: true,
+        tile_entities: true,
+        tile_ticks: true,
+        height_map_biomes: true,
+        light: true,
+        unknown: true
+    };
+    pub const BLOCK_IDS: ChunkFilter = ChunkFilter {
+        blocks: true,
+        block_data: false,
+        entities: false,
+        tile_entities: false,
+        tile_ticks: false,
+        height_map_biomes: false,
+        light: false,
+        unknown: false
+    };
+}
+
+/// Everything in `Level` that [`ChunkData`] or [`Chunk`] holds
+const LEVEL_FIELDS: [&str; 13] = ["xPos", "zPos", "LastUpdate", "InhabitedTime", "TerrainPopulated", "LightPopulated",
+    "V", "Sections", "HeightMap", "Biomes", "Entities", "TileEntities", "TileTicks"];
+const SECTION_FIELDS: [&str; 8] = ["Y", "Blocks", "Add", "Data", "BlockLight", "SkyLight", "Palette", "BlockStates"];
+
 struct ChunkVisitor {
-    data: ChunkData
+    data: ChunkData,
+    /// The section currently being read, `Y` can come after the arrays so it's only filed away once
+    /// the compound ends
+    curr_section: Option<ChunkSection>,
+    /// Entities, tile entities and tile ticks are read whole before picking out the fields, so
+    /// everything inside one is handed off to a tree visitor
+    curr_compound: Option<NbtTreeVisitor>,
+    /// Collect the unmodeled tags of the root, `Level`, and the current section
+    extra: NbtTreeVisitor,
+    level_extra: NbtTreeVisitor,
+    section_extra: NbtTreeVisitor,
+    filter: ChunkFilter,
+    last_update: i64,
+    inhabited_time: i64,
+    terrain_populated: bool,
+    light_populated: bool,
+    version: Option<i8>
 }
 impl ChunkVisitor {
-    fn new() -> ChunkVisitor {
+    fn new(filter: ChunkFilter) -> ChunkVisitor {
         ChunkVisitor {
             data: ChunkData {
                 pos: (0, 0).into(),
-                sections: Vec::new(),
-                entities: Vec::new()
-            }
+                sections: BTreeMap::new(),
+                entities: Vec::new(),
+                tile_entities: Vec::new(),
+                tile_ticks: Vec::new(),
+                height_map: Vec::new(),
+                biomes: Vec::new(),
+                biomes_as_ints: false,
+                extra: NbtCompound::new(),
+                level_extra: NbtCompound::new(),
+                level_types: TagTypes(Some(Vec::new())),
+                level_list_types: TagTypes(Some(Vec::new())),
+                filter
+            },
+            curr_section: None,
+            curr_compound: None,
+            extra: NbtTreeVisitor::new(),
+            level_extra: NbtTreeVisitor::new(),
+            section_extra: NbtTreeVisitor::new(),
+            filter,
+            last_update: 0,
+            inhabited_time: 0,
+            terrain_populated: false,
+            light_populated: false,
+            version: None
         }
     }
 
     #[inline]
     fn visit_level(&mut self, val: LeafTag, path: &NbtPath) -> nbt::Result<()> {
         let Some(NbtPathElement::Element(second_level)) = path.get(2) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
         };
         if second_level == "Sections" {
             self.visit_section(val, path)?;
-        } else if second_level == "Entities" {
-            self.visit_entities(val, path)?;
         } else if second_level == "xPos" {
             let LeafTag::Int(x_pos) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, chunk xPos is not an int".to_string()));
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, chunk xPos is not an int"));
             };
             self.data.pos.x = x_pos;
         } else if second_level == "zPos" {
             let LeafTag::Int(z_pos) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, chunk zPos is not an int".to_string()));
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, chunk zPos is not an int"));
             };
             self.data.pos.z = z_pos;
+        } else if second_level == "HeightMap" {
+            let LeafTag::IntArray(height_map) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, HeightMap not an int array"));
+            };
+            self.data.height_map = height_map;
+        } else if second_level == "Biomes" {
+            self.data.biomes_as_ints = matches!(val, LeafTag::IntArray(_));
+            self.data.biomes = match val {
+                LeafTag::ByteArray(biomes) => biomes.into_iter().map(|biome| biome as u8 as i32).collect(),
+                LeafTag::IntArray(biomes) => biomes,
+                _ => return Err(nbt::NbtError::custom("Unexpected Chunk Structure, Biomes not a byte or int array"))
+            };
+        } else if second_level == "LastUpdate" {
+            let LeafTag::Long(last_update) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, LastUpdate is not a long"));
+            };
+            self.last_update = last_update;
+        } else if second_level == "InhabitedTime" {
+            let LeafTag::Long(inhabited_time) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, InhabitedTime is not a long"));
+            };
+            self.inhabited_time = inhabited_time;
+        } else if second_level == "TerrainPopulated" {
+            let LeafTag::Byte(terrain_populated) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, TerrainPopulated is not a byte"));
+            };
+            self.terrain_populated = terrain_populated != 0;
+        } else if second_level == "LightPopulated" {
+            let LeafTag::Byte(light_populated) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, LightPopulated is not a byte"));
+            };
+            self.light_populated = light_populated != 0;
+        } else if second_level == "V" {
+            let LeafTag::Byte(version) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, V is not a byte"));
+            };
+            self.version = Some(version);
         }
 
         Ok(())
@@ -72,151 +796,622 @@ impl ChunkVisitor {
 
     #[inline]
     fn visit_section(&mut self, val: LeafTag, path: &NbtPath) -> nbt::Result<()> {
-        let Some(NbtPathElement::Index(index)) = path.get(3) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
+        let Some(curr_section) = self.curr_section.as_mut() else {
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
         };
-        let index = *index;
-        if index == self.data.sections.len() {
-            self.data.sections.push(ChunkSection {
-                blocks: Vec::new(),
-                block_data: Vec::new()
-            });
-        }
-        if index >= self.data.sections.len() {
-            return Err(nbt::NbtError::Custom("Unexpected Section Visitor Order".to_string()));
-        }
-        let curr_section = self.data.sections.get_mut(index).unwrap();
         let Some(NbtPathElement::Element(field_name)) = path.get(4) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
         };
 
-        if field_name == "Blocks" {
+        if field_name == "Y" {
+            let LeafTag::Byte(y) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, section Y not a byte"));
+            };
+            curr_section.y = Some(y);
+        } else if field_name == "Blocks" {
             let LeafTag::ByteArray(blocks) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, Blocks not a byte array".to_string()));
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, Blocks not a byte array"));
             };
             curr_section.blocks = blocks;
+        } else if field_name == "Add" {
+            let LeafTag::ByteArray(add) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, Add not a byte array"));
+            };
+            curr_section.add = add;
         } else if field_name == "Data" {
             let LeafTag::ByteArray(block_data) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, Data not a byte array".to_string()));
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, Data not a byte array"));
             };
             curr_section.block_data = block_data;
+        } else if field_name == "BlockLight" {
+            let LeafTag::ByteArray(block_light) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, BlockLight not a byte array"));
+            };
+            curr_section.block_light = block_light;
+        } else if field_name == "SkyLight" {
+            let LeafTag::ByteArray(sky_light) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, SkyLight not a byte array"));
+            };
+            curr_section.sky_light = sky_light;
+        } else if field_name == "BlockStates" {
+            let LeafTag::LongArray(block_states) = val else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, BlockStates not a long array"));
+            };
+            curr_section.block_states = block_states;
         }
 
          Ok(())
     }
 
+    /// Whether the tag at `path` is anything the chunk (with the current filter) actually uses
+    fn is_wanted(&self, path: &NbtPath) -> bool {
+        let Some(NbtPathElement::Element(name)) = path.peek() else {
+            // List elements are wanted if the list is
+            return true;
+        };
+        if Self::is_unmodeled(path) {
+            return self.filter.unknown;
+        }
+        match path.len() {
+            1 => true,
+            2 => name == "Level",
+            3 => match name.as_str() {
+                Ok("xPos") | Ok("zPos") => true,
+                Ok("LastUpdate") | Ok("InhabitedTime") | Ok("TerrainPopulated") | Ok("LightPopulated") | Ok("V") => true,
+                Ok("Sections") => self.filter.blocks || self.filter.block_data || self.filter.light,
+                Ok("HeightMap") | Ok("Biomes") => self.filter.height_map_biomes,
+                Ok("Entities") => self.filter.entities,
+                Ok("TileEntities") => self.filter.tile_entities,
+                Ok("TileTicks") => self.filter.tile_ticks,
+                _ => false
+            },
+            5 if Self::in_level_field(path, "Sections") => match name.as_str() {
+                Ok("Y") => true,
+                Ok("Blocks") | Ok("Add") | Ok("Palette") | Ok("BlockStates") => self.filter.blocks,
+                Ok("Data") => self.filter.block_data,
+                Ok("BlockLight") | Ok("SkyLight") => self.filter.light,
+                _ => false
+            },
+            _ if Self::is_captured(path) => true,
+            _ => false
+        }
+    }
+
+    /// Whether `path` is inside one of the compounds that are read whole
     #[inline]
-    fn visit_entities(&mut self, val: LeafTag, path: &NbtPath) -> nbt::Result<()> {
-        let Some(NbtPathElement::Index(index)) = path.get(3) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
-        };
-        let index = *index;
-        if index == self.data.entities.len() {
-            self.data.entities.push(Entity {
-                id: "".into(),
-                pos: (0.0, 0.0, 0.0),
-                block: None
-            });
-        }
-        if index >= self.data.entities.len() {
-            return Err(nbt::NbtError::Custom("Unexpected Entity Visitor Order".to_string()));
-        }
-        let curr_entity = self.data.entities.get_mut(index).unwrap();
-        let Some(NbtPathElement::Element(field_name)) = path.get(4) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
+    fn is_captured(path: &NbtPath) -> bool {
+        path.len() >= 4 && ["Entities", "TileEntities", "TileTicks"].iter().any(|field| Self::in_level_field(path, field)) ||
+            Self::in_palette(path)
+    }
+
+    /// Whether `path` is inside an entry of `Level.Sections[i].Palette`
+    #[inline]
+    fn in_palette(path: &NbtPath) -> bool {
+        path.len() >= 6 && Self::in_level_field(path, "Sections") &&
+            matches!(path.get(4), Some(NbtPathElement::Element(name)) if name == "Palette")
+    }
+
+    /// Whether `path` is inside a tag none of the fields of the chunk hold
+    fn is_unmodeled(path: &NbtPath) -> bool {
+        let is_field = |index: usize, fields: &[&str]| match path.get(index) {
+            Some(NbtPathElement::Element(name)) => fields.iter().any(|&field| name == field),
+            _ => true
         };
+        if path.len() < 2 {
+            false
+        } else if !Self::in_level(path) {
+            true
+        } else if path.len() < 3 {
+            false
+        } else if !is_field(2, &LEVEL_FIELDS) {
+            true
+        } else {
+            Self::in_level_field(path, "Sections") && path.len() >= 5 && !is_field(4, &SECTION_FIELDS)
+        }
+    }
 
-        if *field_name == JavaString::from("id") {
-            let LeafTag::String(id) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, entity id not string".to_string()));
-            };
-            curr_entity.id = id.into_string()?;
-        } else if *field_name == JavaString::from("Block") {
-            let LeafTag::String(block) = val else {
-                return Err(nbt::NbtError::Custom("Unexpected Chunk Structure, entity Block not string".to_string()));
-            };
-            curr_entity.block = Some(block.into_string()?);
+    /// The visitor collecting the compound `path` is inside of, if any
+    #[inline]
+    fn captured_visitor(&mut self, path: &NbtPath) -> Option<&mut NbtTreeVisitor> {
+        if Self::is_captured(path) {
+            self.curr_compound.as_mut()
+        } else if !Self::is_unmodeled(path) {
+            None
+        } else if !Self::in_level(path) {
+            Some(&mut self.extra)
+        } else if Self::in_level_field(path, "Sections") {
+            Some(&mut self.section_extra)
+        } else {
+            Some(&mut self.level_extra)
         }
+    }
 
-        Ok(())
+    /// Whether `path` is `Level` or something inside it
+    #[inline]
+    fn in_level(path: &NbtPath) -> bool {
+        matches!(path.get(1), Some(NbtPathElement::Element(level)) if level == "Level")
+    }
+
+    /// Whether `path` is `Level.<field>` or something inside it
+    #[inline]
+    fn in_level_field(path: &NbtPath, field: &str) -> bool {
+        Self::in_level(path) && matches!(path.get(2), Some(NbtPathElement::Element(name)) if name == field)
     }
 }
 impl NbtVisitor for ChunkVisitor {
+    #[inline]
+    fn enter_tag(&mut self, tag_id: TagId, path: &NbtPath) -> nbt::Result<Visit> {
+        if path.len() == 3 && Self::in_level(path) {
+            if let Some(NbtPathElement::Element(name)) = path.peek() {
+                if let Some(field) = LEVEL_FIELDS.iter().find(|&&field| name == field) {
+                    self.data.level_types.insert(field, tag_id);
+                }
+            }
+        }
+        Ok(if self.is_wanted(path) { Visit::Continue } else { Visit::Skip })
+    }
+
     #[inline]
     fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> nbt::Result<()> {
         let Some(NbtPathElement::Element(first_level)) = path.get(1) else {
-            return Err(nbt::NbtError::Custom("Unexpected Chunk Structure".to_string()));
+            return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
         };
-        if first_level == "Level" {
+        if let Some(entity) = self.captured_visitor(path) {
+            entity.visit_leaf(val, path)?;
+        } else if first_level == "Level" {
             self.visit_level(val, path)?;
         }
         Ok(())
     }
+
+    fn enter_compound(&mut self, path: &NbtPath) -> nbt::Result<()> {
+        // The root, Level, Level.Sections[i], and the elements of the captured lists
+        if path.len() == 1 {
+            self.extra.enter_compound(path)?;
+        } else if path.len() == 2 && Self::in_level(path) {
+            self.level_extra.enter_compound(path)?;
+        } else if path.len() == 4 {
+            if Self::in_level_field(path, "Sections") {
+                self.curr_section = Some(ChunkSection::default());
+                self.section_extra = NbtTreeVisitor::new();
+                self.section_extra.enter_compound(path)?;
+            } else if Self::is_captured(path) {
+                self.curr_compound = Some(NbtTreeVisitor::new());
+            }
+        } else if path.len() == 6 && Self::in_palette(path) {
+            self.curr_compound = Some(NbtTreeVisitor::new());
+        }
+        if let Some(entity) = self.captured_visitor(path) {
+            entity.enter_compound(path)?;
+        }
+        Ok(())
+    }
+
+    fn exit_compound(&mut self, path: &NbtPath) -> nbt::Result<()> {
+        if let Some(entity) = self.captured_visitor(path) {
+            entity.exit_compound(path)?;
+        }
+        if path.len() == 6 && Self::in_palette(path) {
+            let (Some(section), Some(compound)) = (self.curr_section.as_mut(), self.curr_compound.take()) else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
+            };
+            section.palette.push(BlockState::from_nbt(&compound.into_root())?);
+        } else if path.len() == 4 && Self::is_captured(path) {
+            let Some(compound) = self.curr_compound.take() else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
+            };
+            let compound = compound.into_root();
+            if Self::in_level_field(path, "Entities") {
+                self.data.entities.push(Entity::from_nbt(compound)?);
+            } else if Self::in_level_field(path, "TileEntities") {
+                self.data.tile_entities.push(TileEntity::from_nbt(compound)?);
+            } else {
+                self.data.tile_ticks.push(ScheduledTick::from_nbt(compound)?);
+            }
+        } else if path.len() == 4 && Self::in_level_field(path, "Sections") {
+            let Some(mut section) = self.curr_section.take() else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure"));
+            };
+            let Some(y) = section.y else {
+                return Err(nbt::NbtError::custom("Unexpected Chunk Structure, section has no Y"));
+            };
+            self.section_extra.exit_compound(path)?;
+            section.extra = std::mem::take(&mut self.section_extra).into_root();
+            self.data.sections.insert(y, section);
+        } else if path.len() == 2 && Self::in_level(path) {
+            self.level_extra.exit_compound(path)?;
+            self.data.level_extra = std::mem::take(&mut self.level_extra).into_root();
+        } else if path.len() == 1 {
+            self.extra.exit_compound(path)?;
+            self.data.extra = std::mem::take(&mut self.extra).into_root();
+        }
+        Ok(())
+    }
+
+    fn enter_list(&mut self, elem_type: TagId, len: usize, path: &NbtPath) -> nbt::Result<()> {
+        if let Some(entity) = self.captured_visitor(path) {
+            entity.enter_list(elem_type, len, path)?;
+        } else if path.len() == 3 && Self::in_level(path) {
+            if let Some(NbtPathElement::Element(name)) = path.peek() {
+                if let Some(field) = LEVEL_FIELDS.iter().find(|&&field| name == field) {
+                    self.data.level_list_types.insert(field, elem_type);
+                }
+            }
+            if Self::in_level_field(path, "Entities") {
+                self.data.entities.reserve(len.min(1024));
+            }
+        }
+        Ok(())
+    }
+
+    fn exit_list(&mut self, path: &NbtPath) -> nbt::Result<()> {
+        if let Some(entity) = self.captured_visitor(path) {
+            entity.exit_list(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ChunkErrorKind {
+    #[error("Unknown Chunk Compression Type {0}")]
+    UnknownCompression(u8),
+    #[error("Chunk Was Parsed With A Filter, Writing It Would Lose Data")]
+    Filtered,
+    #[error(transparent)]
+    NbtError(#[from] nbt::NbtError),
+    #[error(transparent)]
+    IoError(#[from] io::Error)
+}
+
+/// A [`ChunkErrorKind`] along with which chunk it happened in, and how that chunk was compressed.
+/// The position is only known when the chunk was read out of a region, and is absolute (see
+/// [`Region`](crate::region::Region) for regions that don't know where they are).
+#[derive(Debug)]
+pub struct ChunkError {
+    kind: ChunkErrorKind,
+    pos: Option<ChunkPos>,
+    compression_type: Option<u8>
+}
+
+impl ChunkError {
+    pub fn kind(&self) -> &ChunkErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ChunkErrorKind {
+        self.kind
+    }
+
+    pub fn pos(&self) -> Option<ChunkPos> {
+        self.pos
+    }
+
+    pub fn compression_type(&self) -> Option<u8> {
+        self.compression_type
+    }
+
+    pub(crate) fn with_pos(mut self, pos: ChunkPos) -> ChunkError {
+        self.pos = Some(pos);
+        self
+    }
+
+    fn with_compression_type(mut self, compression_type: u8) -> ChunkError {
+        self.compression_type = Some(compression_type);
+        self
+    }
+}
+
+impl Display for ChunkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(pos) = self.pos {
+            write!(f, " in chunk ({}, {})", pos.x, pos.z)?;
+        }
+        if let Some(compression_type) = self.compression_type {
+            write!(f, " (compression type {compression_type})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ChunkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&self.kind)
+    }
+}
+
+impl From<ChunkErrorKind> for ChunkError {
+    fn from(kind: ChunkErrorKind) -> Self {
+        ChunkError {
+            kind,
+            pos: None,
+            compression_type: None
+        }
+    }
+}
+
+impl From<nbt::NbtError> for ChunkError {
+    fn from(value: nbt::NbtError) -> Self {
+        ChunkErrorKind::from(value).into()
+    }
+}
+
+impl From<io::Error> for ChunkError {
+    fn from(value: io::Error) -> Self {
+        ChunkErrorKind::from(value).into()
+    }
 }
 
 impl Chunk {
-    pub fn parse<R: Read>(reader: &mut R) -> Result<Chunk, Error> {
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Chunk, ChunkError> {
+        Self::parse_filtered(reader, ChunkFilter::ALL)
+    }
+
+    pub fn parse_filtered<R: Read>(reader: &mut R, filter: ChunkFilter) -> Result<Chunk, ChunkError> {
         let length = reader.read_u32::<BigEndian>()?;
         let compression_type = reader.read_u8()?;
 
-        let mut visitor = ChunkVisitor::new();
-        match compression_type {
-            1 => {
-                let mut reader = GzDecoder::new(reader);
-                visit_nbt(&mut reader, &mut visitor).unwrap();
-            }
-            2 => {
-                let mut reader = ZlibDecoder::new(reader);
-                visit_nbt(&mut reader, &mut visitor).unwrap();
-            }
-            3 => {
-                visit_nbt(reader, &mut visitor).unwrap();
-            }
-            _ => panic!("Unknown compression type {}", compression_type)
+        let mut visitor = ChunkVisitor::new(filter);
+        let result = match compression_type {
+            1 => visit_nbt(&mut GzDecoder::new(reader), &mut visitor),
+            2 => visit_nbt(&mut ZlibDecoder::new(reader), &mut visitor),
+            3 => visit_nbt(reader, &mut visitor),
+            _ => return Err(ChunkError::from(ChunkErrorKind::UnknownCompression(compression_type)))
         };
+        result.map_err(|err| ChunkError::from(err).with_compression_type(compression_type))?;
 
         Ok(Chunk {
             length,
             compression_type,
+            last_update: visitor.last_update,
+            inhabited_time: visitor.inhabited_time,
+            terrain_populated: visitor.terrain_populated,
+            light_populated: visitor.light_populated,
+            version: visitor.version,
             data: visitor.data
         })
     }
 
-    pub fn block_at(&self, pos: BlockPos) -> u8 {
-        let subchunk = (pos.y >> 4) as usize;
-        if let Some(section) = self.data.sections.get(subchunk) {
-            let blocks = &section.blocks;
-            let x = pos.x & 0xf;
-            let y = pos.y & 0xf;
-            let z = pos.z & 0xf;
-            let index = (x | (y << 8) | (z << 4)) as usize;
-            blocks[index] as u8
-        } else {
-            0
+    /// The chunk's position in the world, from its `xPos` and `zPos`
+    pub fn pos(&self) -> ChunkPos {
+        self.data.pos
+    }
+
+    pub fn block_at(&self, pos: BlockPos) -> Block {
+        self.section_at(pos).map_or(Block::AIR, |(section, index)| section.block(index))
+    }
+
+    pub fn data_at(&self, pos: BlockPos) -> u8 {
+        self.block_at(pos).data
+    }
+
+    /// The height map value for the column containing `x`, `z`: the lowest y that full strength sky
+    /// light reaches. `None` if the chunk didn't have a height map (or it was filtered out).
+    pub fn height_at(&self, x: i32, z: i32) -> Option<i32> {
+        self.data.height_map.get(Self::column_index(x, z)).copied()
+    }
+
+    /// The biome id of the column containing `x`, `z`. `None` if the chunk has no biomes, or the
+    /// biome hasn't been generated yet (stored as 255).
+    pub fn biome_at(&self, x: i32, z: i32) -> Option<i32> {
+        let biome = *self.data.biomes.get(Self::column_index(x, z))?;
+        (biome != 255).then_some(biome)
+    }
+
+    pub fn block_light_at(&self, pos: BlockPos) -> u8 {
+        self.section_at(pos).map_or(0, |(section, index)| nibble(&section.block_light, index))
+    }
+
+    pub fn sky_light_at(&self, pos: BlockPos) -> u8 {
+        match self.section_at(pos) {
+            Some((section, index)) => nibble(&section.sky_light, index),
+            // Sections without any blocks aren't saved, the game treats them as fully lit if they're
+            // above the height map and dark otherwise
+            None => self.height_at(pos.x, pos.z).map_or(0, |height| if pos.y >= height { 15 } else { 0 })
         }
     }
 
     pub fn block_iter(&self) -> impl Iterator<Item=(BlockPos, Block)> + '_ {
-        self.data.sections.iter().enumerate().flat_map(|(subchunk, section)| {
-            (0..4096).map(move |index| {
-                let block_id = section.blocks[index] as u8;
-                let block_data_byte = section.block_data[index >> 1] as u8;
-                let data = if index & 1 == 0 {
-                    block_data_byte & 0xf
-                } else {
-                    block_data_byte >> 4 & 0xf
-                };
-
-                let block_x = (index & 0xf) as i32;
-                let block_y = (subchunk as i32) << 4 | ((index >> 8) & 0xf) as i32;
-                let block_z = ((index >> 4) & 0xf) as i32;
-
-                ((block_x, block_y, block_z).into(), Block::new(block_id, data))
-            })
+        self.data.sections.iter().flat_map(|(&subchunk, section)| {
+            (0..4096).map(move |index| (Self::index_pos(subchunk, index), section.block(index)))
         })
     }
 
+    /// The block state at `pos` in a 1.13+ chunk. `None` if there's no section there, or it's in the
+    /// pre 1.13 format that [`Chunk::block_at`] reads.
+    pub fn block_state_at(&self, pos: BlockPos) -> Option<&BlockState> {
+        let (section, index) = self.section_at(pos)?;
+        section.block_state(index)
+    }
+
+    /// Like [`Chunk::block_iter`], for the sections in the 1.13+ palette format
+    pub fn block_state_iter(&self) -> impl Iterator<Item=(BlockPos, &BlockState)> + '_ {
+        self.data.sections.iter().flat_map(|(&subchunk, section)| {
+            (0..4096).filter_map(move |index| Some((Self::index_pos(subchunk, index), section.block_state(index)?)))
+        })
+    }
+
+    /// Whether the chunk was saved in 1.13 or later, after block ids were replaced by block states
+    fn is_flattened(&self) -> bool {
+        // 17w47a, the snapshot that introduced the palette format
+        self.data.extra.get("DataVersion").and_then(NbtTag::as_int).is_some_and(|version| version >= 1451) ||
+            self.data.sections.values().any(|section| !section.palette.is_empty())
+    }
+
+    #[inline]
+    fn column_index(x: i32, z: i32) -> usize {
+        ((z & 0xf) << 4 | (x & 0xf)) as usize
+    }
+
+    /// The section containing `pos`, along with the index of `pos` in its arrays
+    fn section_at(&self, pos: BlockPos) -> Option<(&ChunkSection, usize)> {
+        let subchunk = i8::try_from(pos.y >> 4).ok()?;
+        let section = self.data.sections.get(&subchunk)?;
+        Some((section, Self::section_index(pos)))
+    }
+
+    /// The chunk relative position of `index` in section `subchunk`
+    #[inline]
+    fn index_pos(subchunk: i8, index: usize) -> BlockPos {
+        let block_x = (index & 0xf) as i32;
+        let block_y = (subchunk as i32) << 4 | ((index >> 8) & 0xf) as i32;
+        let block_z = ((index >> 4) & 0xf) as i32;
+        (block_x, block_y, block_z).into()
+    }
+
+    #[inline]
+    fn section_index(pos: BlockPos) -> usize {
+        let x = pos.x & 0xf;
+        let y = pos.y & 0xf;
+        let z = pos.z & 0xf;
+        (x | (y << 8) | (z << 4)) as usize
+    }
+
     pub fn entity_iter(&self) -> impl Iterator<Item=&Entity> + '_ {
         self.data.entities.iter()
     }
+
+    pub fn tile_entity_iter(&self) -> impl Iterator<Item=&TileEntity> + '_ {
+        self.data.tile_entities.iter()
+    }
+
+    pub fn tile_entity_at(&self, pos: BlockPos) -> Option<&TileEntity> {
+        self.data.tile_entities.iter().find(|tile_entity| tile_entity.pos == pos)
+    }
+
+    pub fn tile_tick_iter(&self) -> impl Iterator<Item=&ScheduledTick> + '_ {
+        self.data.tile_ticks.iter()
+    }
+
+    /// Sets the block at `pos`, adding an empty section if there isn't one yet. Only the low 12 bits
+    /// of the id can be stored. Returns false (and does nothing) if `pos.y` is outside the range a
+    /// chunk can hold, or the chunk is in the 1.13+ format which doesn't have block ids.
+    pub fn set_block(&mut self, pos: BlockPos, block: Block) -> bool {
+        if self.is_flattened() {
+            return false;
+        }
+        let Ok(subchunk) = i8::try_from(pos.y >> 4) else {
+            return false;
+        };
+        let section = self.data.sections.entry(subchunk).or_insert_with(|| ChunkSection::empty(subchunk));
+        section.set_block(Self::section_index(pos), block);
+        true
+    }
+
+    pub fn add_entity(&mut self, entity: Entity) {
+        self.data.entities.push(entity);
+    }
+
+    /// Removes every entity `predicate` returns true for, returning them
+    pub fn remove_entities<F: FnMut(&Entity) -> bool>(&mut self, mut predicate: F) -> Vec<Entity> {
+        let (removed, kept) = std::mem::take(&mut self.data.entities).into_iter().partition(|entity| predicate(entity));
+        self.data.entities = kept;
+        removed
+    }
+
+    /// Adds a tile entity, replacing (and returning) any that was already at the same position
+    pub fn set_tile_entity(&mut self, tile_entity: TileEntity) -> Option<TileEntity> {
+        let old = self.remove_tile_entity(tile_entity.pos);
+        self.data.tile_entities.push(tile_entity);
+        old
+    }
+
+    pub fn remove_tile_entity(&mut self, pos: BlockPos) -> Option<TileEntity> {
+        let index = self.data.tile_entities.iter().position(|tile_entity| tile_entity.pos == pos)?;
+        Some(self.data.tile_entities.remove(index))
+    }
+
+    /// Serializes the chunk the way it's stored in a region file: the length, the compression type
+    /// (1 for gzip, 2 for zlib or 3 for uncompressed), then the NBT. Tags that aren't modeled are
+    /// written back as they were read, but the chunk has to have been parsed with
+    /// [`ChunkFilter::ALL`], anything filtered out would be lost.
+    pub fn write<W: Write>(&self, writer: &mut W, compression_type: u8) -> Result<(), ChunkError> {
+        if self.data.filter != ChunkFilter::ALL {
+            return Err(ChunkErrorKind::Filtered.into());
+        }
+        let nbt = self.write_nbt(NbtWriter::new(Vec::new()))?.finish()?;
+        let payload = match compression_type {
+            1 => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&nbt)?;
+                encoder.finish()?
+            }
+            2 => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&nbt)?;
+                encoder.finish()?
+            }
+            3 => nbt,
+            _ => return Err(ChunkErrorKind::UnknownCompression(compression_type).into())
+        };
+
+        writer.write_u32::<BigEndian>(payload.len() as u32 + 1)?;
+        writer.write_u8(compression_type)?;
+        writer.write_all(&payload)?;
+        Ok(())
+    }
+
+    fn write_nbt<W: Write>(&self, mut writer: NbtWriter<W>) -> nbt::Result<NbtWriter<W>> {
+        let data = &self.data;
+        let types = &data.level_types;
+        writer.begin_compound("")?;
+        data.extra.write_entries(&mut writer)?;
+        writer.begin_compound("Level")?;
+        if let Some(version) = self.version {
+            writer.write_leaf("V", &LeafTag::Byte(version))?;
+        }
+        writer.write_leaf("xPos", &LeafTag::Int(data.pos.x))?;
+        writer.write_leaf("zPos", &LeafTag::Int(data.pos.z))?;
+        // Only what the chunk had, or has been given since. 1.13 dropped the population flags for
+        // example, and empty tile tick lists aren't saved at all.
+        if types.keep("LastUpdate", self.last_update == 0) {
+            writer.write_leaf("LastUpdate", &LeafTag::Long(self.last_update))?;
+        }
+        if types.keep("HeightMap", data.height_map.is_empty()) {
+            writer.write_leaf("HeightMap", &LeafTag::IntArray(data.height_map.clone()))?;
+        }
+        if types.keep("TerrainPopulated", !self.terrain_populated) {
+            writer.write_leaf("TerrainPopulated", &LeafTag::Byte(self.terrain_populated as i8))?;
+        }
+        if types.keep("LightPopulated", !self.light_populated) {
+            writer.write_leaf("LightPopulated", &LeafTag::Byte(self.light_populated as i8))?;
+        }
+        if types.keep("InhabitedTime", self.inhabited_time == 0) {
+            writer.write_leaf("InhabitedTime", &LeafTag::Long(self.inhabited_time))?;
+        }
+
+        if types.keep("Sections", data.sections.is_empty()) {
+            writer.begin_list("Sections", data.list_type("Sections", data.sections.is_empty()), data.sections.len())?;
+            for section in data.sections.values() {
+                section.write(&mut writer)?;
+            }
+            writer.end_list()?;
+        }
+
+        if types.keep("Biomes", data.biomes.is_empty()) {
+            let biomes = if data.biomes_as_ints {
+                LeafTag::IntArray(data.biomes.clone())
+            } else {
+                LeafTag::ByteArray(data.biomes.iter().map(|&biome| biome as i8).collect())
+            };
+            writer.write_leaf("Biomes", &biomes)?;
+        }
+
+        let entities = data.entities.iter().map(Entity::to_nbt);
+        let tile_entities = data.tile_entities.iter().map(TileEntity::to_nbt);
+        let tile_ticks = data.tile_ticks.iter().map(ScheduledTick::to_nbt);
+        for (name, compounds) in [("Entities", compound_list_tag(entities)), ("TileEntities", compound_list_tag(tile_entities)),
+                                  ("TileTicks", compound_list_tag(tile_ticks))] {
+            if types.keep(name, compounds.is_empty()) {
+                let compounds = if compounds.is_empty() { NbtList::with_type(data.list_type(name, true)) } else { compounds };
+                NbtTag::List(compounds).write_named(&mut writer, name)?;
+            }
+        }
+
+        data.level_extra.write_entries(&mut writer)?;
+        writer.end_compound()?;
+        writer.end_compound()?;
+        Ok(writer)
+    }
 }
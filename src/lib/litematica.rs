@@ -1,9 +1,11 @@
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::io::Error;
-use quartz_nbt::{compound, io, NbtCompound, NbtList};
-use quartz_nbt::io::Flavor;
+use std::io::Write;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use crate::nbt;
+use crate::nbt::{LeafTag, NbtWriter, TagId};
 use crate::positions::BlockPos;
 
 // This whole file is a transliteration of LitematicStructureBuilder.java from earth's falling
@@ -213,37 +215,45 @@ impl LitematicaRegionBuilder {
         }
     }
 
-    fn to_nbt(&self, origin: BlockPos) -> NbtCompound {
-        let enclosing_size = compound! {
-            "x": self.max_x - self.min_x  + 1,
-            "y": self.max_y - self.min_y  + 1,
-            "z": self.max_z - self.min_z  + 1,
-        };
-        let mut block_state_palette = NbtList::new();
-        for _ in 0..self.palette.len() {
-            block_state_palette.push(NbtCompound::new());
-        }
-        for key in self.palette.keys() {
-            let index = self.palette[key];
-            let block_state: &mut NbtCompound = block_state_palette.get_mut(index as usize).unwrap();
-            block_state.insert("Name", key.block.clone());
-            let mut properties = NbtCompound::new();
-            for property_key in key.properties.keys() {
-                let property_value = key.properties[property_key].clone();
-                properties.insert(property_key, property_value);
+    fn write_nbt<W: Write>(&self, writer: &mut NbtWriter<W>, name: &str, origin: BlockPos) -> nbt::Result<()> {
+        writer.begin_compound(name)?;
+
+        writer.begin_compound("Position")?;
+        writer.write_leaf("x", &LeafTag::Int(self.min_x - origin.x))?;
+        writer.write_leaf("y", &LeafTag::Int(self.min_y - origin.y))?;
+        writer.write_leaf("z", &LeafTag::Int(self.min_z - origin.z))?;
+        writer.end_compound()?;
+
+        writer.begin_compound("Size")?;
+        writer.write_leaf("x", &LeafTag::Int(self.max_x - self.min_x + 1))?;
+        writer.write_leaf("y", &LeafTag::Int(self.max_y - self.min_y + 1))?;
+        writer.write_leaf("z", &LeafTag::Int(self.max_z - self.min_z + 1))?;
+        writer.end_compound()?;
+
+        writer.begin_list("TileEntities", TagId::End, 0)?;
+        writer.end_list()?;
+        writer.begin_list("Entities", TagId::End, 0)?;
+        writer.end_list()?;
+
+        // The palette has to be written out in index order
+        let mut block_state_palette: Vec<(&BlockState, i32)> = self.palette.iter().map(|(k, v)| (k, *v)).collect();
+        block_state_palette.sort_by_key(|(_, index)| *index);
+        writer.begin_list("BlockStatePalette", TagId::Compound, block_state_palette.len())?;
+        for (block_state, _) in block_state_palette {
+            writer.begin_compound_element()?;
+            writer.write_leaf("Name", &LeafTag::String(block_state.block.as_str().into()))?;
+            writer.begin_compound("Properties")?;
+            for (property_key, property_value) in &block_state.properties {
+                writer.write_leaf(property_key, &LeafTag::String(property_value.as_str().into()))?;
             }
-            block_state.insert("Properties", properties);
+            writer.end_compound()?;
+            writer.end_compound()?;
         }
-        let region = compound! {
-            "Position": compound! { "x": self.min_x - origin.x, "y": self.min_y - origin.y, "z": self.min_z - origin.z },
-            "Size": enclosing_size,
-            "TileEntities": NbtList::new(),
-            "Entities": NbtList::new(),
-            "BlockStatePalette": NbtList::from(block_state_palette),
-            "BlockStates": self.storage.long_array.clone()
-        };
+        writer.end_list()?;
 
-        region
+        writer.write_leaf("BlockStates", &LeafTag::LongArray(self.storage.long_array.clone()))?;
+
+        writer.end_compound()
     }
 
     fn total_blocks(&self) -> i32 {
@@ -320,48 +330,49 @@ impl LitematicaBuilder {
         BlockPos::new(extents.min_x, extents.min_y, extents.min_z)
     }
 
-    pub fn save(&self, path: &str, name: &str) -> Result<(), Error> {
-
-        let mut regions = NbtCompound::new();
-
+    pub fn save(&self, path: &str, name: &str) -> nbt::Result<()> {
         let mut total_blocks = 0;
         let mut total_volume = 0;
 
         let extents = self.get_extents();
         let origin = BlockPos::new(extents.min_x, extents.min_y, extents.min_z);
 
-        for (name, region) in &self.regions {
+        for region in self.regions.values() {
             total_blocks += region.total_blocks();
             total_volume += ((region.max_x - region.min_x  + 1) as i64) * ((region.max_y - region.min_y  + 1) as i64) * ((region.max_z - region.min_z  + 1) as i64);
-            regions.insert(name, region.to_nbt(origin));
         }
 
-        let enclosing_size = compound! {
-            "x": extents.max_x - extents.min_x + 1,
-            "y": extents.max_y - extents.min_y + 1,
-            "z": extents.max_z - extents.min_z + 1,
-        };
-        let metadata = compound! {
-            "TimeCreated": 10101010101i64,
-            "TimeModified": 10101010101i64,
-            "EnclosingSize": enclosing_size.clone(),
-            "Description": "",
-            "RegionCount": regions.len() as i32,
-            "TotalBlocks": total_blocks,
-            "Author": "rpm0618",
-            "TotalVolume": total_volume,
-            "Name": name
-        };
+        let mut writer = NbtWriter::new(GzEncoder::new(Vec::new(), Compression::default()));
+        writer.begin_compound("")?;
+        writer.write_leaf("MinecraftDataVersion", &LeafTag::Int(1343))?;
+        writer.write_leaf("Version", &LeafTag::Int(4))?;
+
+        writer.begin_compound("Metadata")?;
+        writer.write_leaf("TimeCreated", &LeafTag::Long(10101010101))?;
+        writer.write_leaf("TimeModified", &LeafTag::Long(10101010101))?;
+        writer.begin_compound("EnclosingSize")?;
+        writer.write_leaf("x", &LeafTag::Int(extents.max_x - extents.min_x + 1))?;
+        writer.write_leaf("y", &LeafTag::Int(extents.max_y - extents.min_y + 1))?;
+        writer.write_leaf("z", &LeafTag::Int(extents.max_z - extents.min_z + 1))?;
+        writer.end_compound()?;
+        writer.write_leaf("Description", &LeafTag::String("".into()))?;
+        writer.write_leaf("RegionCount", &LeafTag::Int(self.regions.len() as i32))?;
+        writer.write_leaf("TotalBlocks", &LeafTag::Int(total_blocks))?;
+        writer.write_leaf("Author", &LeafTag::String("rpm0618".into()))?;
+        writer.write_leaf("TotalVolume", &LeafTag::Long(total_volume))?;
+        writer.write_leaf("Name", &LeafTag::String(name.into()))?;
+        writer.end_compound()?;
+
+        writer.begin_compound("Regions")?;
+        for (name, region) in &self.regions {
+            region.write_nbt(&mut writer, name, origin)?;
+        }
+        writer.end_compound()?;
 
-        let root_tag = compound! {
-            "MinecraftDataVersion": 1343,
-            "Version": 4,
-            "Metadata": metadata,
-            "Regions": regions
-        };
+        writer.end_compound()?;
 
-        let mut data: Vec<u8> = Vec::new();
-        io::write_nbt(&mut data, None, &root_tag, Flavor::GzCompressed).expect("NBT Write Failed");
-        std::fs::write(path, &data)
+        let data = writer.finish()?.finish()?;
+        std::fs::write(path, &data)?;
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+use std::io::Read;
+use byteorder::ReadBytesExt;
+use serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::de::value::{BytesDeserializer, StrDeserializer};
+use serde::forward_to_deserialize_any;
+use java_string::JavaString;
+use crate::nbt::{NbtError, NbtErrorKind, NbtFlavor, NbtLimits, NbtPath, Result, TagId};
+use crate::nbt::NbtPathElement::{Element, Index};
+use crate::nbt::reader::{min_tag_size, TagReader};
+use crate::util::read_bytes;
+
+/// Deserializes `T` straight from an NBT stream, without building a tree first. The root compound
+/// maps onto a struct (or map), and fields the type doesn't mention are skipped over unread.
+pub fn from_reader<R: Read, T: DeserializeOwned>(reader: R) -> Result<T> {
+    from_reader_with(reader, NbtFlavor::Java, NbtLimits::default())
+}
+
+pub fn from_reader_with<R: Read, T: DeserializeOwned>(reader: R, flavor: NbtFlavor, limits: NbtLimits) -> Result<T> {
+    let mut deserializer = NbtDeserializer::new_with(reader, flavor, limits);
+    T::deserialize(&mut deserializer)
+}
+
+/// A [`serde::Deserializer`] over an NBT stream. Compounds deserialize as maps/structs, lists and the
+/// typed arrays as sequences, and strings as `str` when they're valid UTF-8 or as bytes when they
+/// aren't (which [`JavaString`] accepts). Bytes can also be read as `bool`s, and strings as unit
+/// enum variants.
+pub struct NbtDeserializer<R> {
+    reader: TagReader<R>,
+    path: NbtPath,
+}
+
+impl<R: Read> NbtDeserializer<R> {
+    pub fn new(reader: R) -> Self {
+        Self::new_with(reader, NbtFlavor::Java, NbtLimits::default())
+    }
+
+    pub fn new_with(reader: R, flavor: NbtFlavor, limits: NbtLimits) -> Self {
+        NbtDeserializer {
+            reader: TagReader::new(reader, flavor, limits),
+            path: NbtPath::new(),
+        }
+    }
+
+    fn read_root(&mut self) -> Result<()> {
+        let root_name = self.reader.read_root()?;
+        self.path.push(Element(root_name));
+        Ok(())
+    }
+}
+
+impl<'de, R: Read> serde::Deserializer<'de> for &mut NbtDeserializer<R> {
+    type Error = NbtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let result = self.read_root()
+            .and_then(|_| TagDeserializer { de: &mut *self, tag_id: TagId::Compound }.deserialize_any(visitor));
+        result.map_err(|err| err.with_location(&self.path, Some(self.reader.offset)))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Deserializes the body of a single tag, whose id has already been read
+struct TagDeserializer<'a, R> {
+    de: &'a mut NbtDeserializer<R>,
+    tag_id: TagId,
+}
+
+impl<'de, R: Read> serde::Deserializer<'de> for TagDeserializer<'_, R> {
+    type Error = NbtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let reader = &mut self.de.reader;
+        reader.check_total_bytes(0)?;
+
+        match self.tag_id {
+            TagId::Byte => visitor.visit_i8(reader.read_i8()?),
+            TagId::Short => visitor.visit_i16(reader.read_short()?),
+            TagId::Int => visitor.visit_i32(reader.read_int()?),
+            TagId::Long => visitor.visit_i64(reader.read_long()?),
+            TagId::Float => visitor.visit_f32(reader.read_float()?),
+            TagId::Double => visitor.visit_f64(reader.read_double()?),
+            TagId::String => {
+                let val = reader.read_string()?;
+                match val.as_str() {
+                    Ok(val) => visitor.visit_str(val),
+                    Err(_) => visitor.visit_bytes(val.as_bytes()),
+                }
+            }
+            TagId::ByteArray => {
+                let len = reader.read_length(1)?;
+                self.de.visit_list(TagId::Byte, len, visitor)
+            }
+            TagId::IntArray => {
+                let len = reader.read_length(4)?;
+                self.de.visit_list(TagId::Int, len, visitor)
+            }
+            TagId::LongArray => {
+                let len = reader.read_length(8)?;
+                self.de.visit_list(TagId::Long, len, visitor)
+            }
+            TagId::List => {
+                let elem_type = reader.read_u8()?.try_into()?;
+                let len = reader.read_length(min_tag_size(elem_type))?;
+                reader.enter()?;
+                let val = self.de.visit_list(elem_type, len, visitor)?;
+                self.de.reader.exit();
+                Ok(val)
+            }
+            TagId::Compound => {
+                reader.enter()?;
+                let mut access = CompoundAccess { de: &mut *self.de, tag_id: None, done: false };
+                let val = visitor.visit_map(&mut access)?;
+                // Whatever the visitor didn't ask for still has to be read past
+                if !access.done {
+                    while access.next_key::<serde::de::IgnoredAny>()?.is_some() {
+                        access.next_value::<serde::de::IgnoredAny>()?;
+                    }
+                }
+                self.de.reader.exit();
+                Ok(val)
+            }
+            TagId::End => Err(NbtErrorKind::InvalidNbtEndTag.into())
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.tag_id {
+            TagId::Byte => visitor.visit_bool(self.de.reader.read_i8()? != 0),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.tag_id {
+            TagId::ByteArray => {
+                let len = self.de.reader.read_length(1)?;
+                visitor.visit_byte_buf(read_bytes(&mut self.de.reader, len)?)
+            }
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    /// A tag being present at all means `Some`, missing fields are `None`
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        match self.tag_id {
+            TagId::String => {
+                let val = self.de.reader.read_string()?;
+                let val = val.into_string().map_err(NbtErrorKind::InvalidModifiedUtf8)?;
+                visitor.visit_enum(val.into_deserializer())
+            }
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.de.reader.skip_tag_body(self.tag_id)?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+impl<R: Read> NbtDeserializer<R> {
+    fn visit_list<'de, V: Visitor<'de>>(&mut self, elem_type: TagId, len: usize, visitor: V) -> Result<V::Value> {
+        let mut access = ListAccess { de: &mut *self, elem_type, len, index: 0 };
+        let val = visitor.visit_seq(&mut access)?;
+        // Tuples and fixed size arrays can stop early, skip the rest
+        for _ in access.index..len {
+            self.reader.skip_tag_body(elem_type)?;
+        }
+        Ok(val)
+    }
+}
+
+struct ListAccess<'a, R> {
+    de: &'a mut NbtDeserializer<R>,
+    elem_type: TagId,
+    len: usize,
+    index: usize,
+}
+
+impl<'de, R: Read> SeqAccess<'de> for ListAccess<'_, R> {
+    type Error = NbtError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        self.de.path.push(Index(self.index));
+        let val = seed.deserialize(TagDeserializer { de: &mut *self.de, tag_id: self.elem_type })?;
+        self.de.path.pop();
+        self.index += 1;
+        Ok(Some(val))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.index)
+    }
+}
+
+struct CompoundAccess<'a, R> {
+    de: &'a mut NbtDeserializer<R>,
+    tag_id: Option<TagId>,
+    done: bool,
+}
+
+impl<'de, R: Read> MapAccess<'de> for CompoundAccess<'_, R> {
+    type Error = NbtError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.done {
+            return Ok(None);
+        }
+        let tag_id: TagId = self.de.reader.read_u8()?.try_into()?;
+        if tag_id == TagId::End {
+            self.done = true;
+            return Ok(None);
+        }
+        let name = self.de.reader.read_string()?;
+        let key = deserialize_key(seed, &name)?;
+        self.de.path.push(Element(name));
+        self.tag_id = Some(tag_id);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let Some(tag_id) = self.tag_id.take() else {
+            return Err(NbtError::custom("next_value called before next_key"));
+        };
+        let val = seed.deserialize(TagDeserializer { de: &mut *self.de, tag_id })?;
+        self.de.path.pop();
+        Ok(val)
+    }
+}
+
+fn deserialize_key<'de, K: DeserializeSeed<'de>>(seed: K, name: &JavaString) -> Result<K::Value> {
+    match name.as_str() {
+        Ok(name) => seed.deserialize(StrDeserializer::new(name)),
+        Err(_) => seed.deserialize(BytesDeserializer::new(name.as_bytes())),
+    }
+}
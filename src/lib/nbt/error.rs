@@ -1,17 +1,38 @@
+use std::fmt::{Display, Formatter};
 use std::io;
 use java_string::Utf8Error;
 use num_enum::TryFromPrimitiveError;
 use thiserror::Error;
-use crate::nbt::TagId;
+use crate::nbt::{NbtPath, TagId};
 
 #[derive(Error, Debug)]
-pub enum NbtError {
+pub enum NbtErrorKind {
     #[error("Invalid NBT Tag ID {0:}")]
     InvalidTagId(#[from] TryFromPrimitiveError<TagId>),
     #[error("Invalid Nbt Root Tag {0:?}")]
     InvalidNbtRoot(TagId),
     #[error("Unexpected End Tag")]
     InvalidNbtEndTag,
+    #[error("List Type Mismatch, expected {expected:?} but found {found:?}")]
+    ListTypeMismatch { expected: TagId, found: TagId },
+    #[error("String Too Long ({0} bytes)")]
+    StringTooLong(usize),
+    #[error("Length Out Of Range ({0})")]
+    LengthOutOfRange(usize),
+    #[error("Negative Length ({0})")]
+    NegativeLength(i32),
+    #[error("Nesting Depth Limit Exceeded (max {0})")]
+    DepthLimitExceeded(usize),
+    #[error("Array Length Limit Exceeded ({len}, max {limit})")]
+    ArrayLengthLimitExceeded { len: usize, limit: usize },
+    #[error("Byte Limit Exceeded (max {0})")]
+    ByteLimitExceeded(u64),
+    #[error("Unrecognized NBT Compression, stream starts with {0:02x?}")]
+    UnknownCompression(Vec<u8>),
+    #[error("Invalid NBT Writer State: {0}")]
+    InvalidWriterState(&'static str),
+    #[error("Invalid SNBT at {pos}: {message}")]
+    InvalidSnbt { message: String, pos: usize },
     #[error("Invalid Modified Utf8 String")]
     InvalidModifiedUtf8(#[from] Utf8Error),
     #[error(transparent)]
@@ -20,4 +41,103 @@ pub enum NbtError {
     Custom(String)
 }
 
+/// An [`NbtErrorKind`] along with where in the NBT it happened. Errors coming out of
+/// [`visit_nbt`](super::visit_nbt) always have the path of the tag being read and the byte offset
+/// into the (decompressed) stream; errors from building or writing NBT generally don't.
+#[derive(Debug)]
+pub struct NbtError(Box<ErrorInner>);
+
+/// Boxed so that `Result<T>` stays small, the reader recurses once per level of nesting
+#[derive(Debug)]
+struct ErrorInner {
+    kind: NbtErrorKind,
+    path: NbtPath,
+    offset: Option<u64>
+}
+
+impl NbtError {
+    pub fn custom<S: Into<String>>(message: S) -> NbtError {
+        NbtErrorKind::Custom(message.into()).into()
+    }
+
+    pub fn kind(&self) -> &NbtErrorKind {
+        &self.0.kind
+    }
+
+    pub fn into_kind(self) -> NbtErrorKind {
+        self.0.kind
+    }
+
+    pub fn path(&self) -> &NbtPath {
+        &self.0.path
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.0.offset
+    }
+
+    /// Fills in the location, unless a more specific one has already been recorded
+    pub(crate) fn with_location(mut self, path: &NbtPath, offset: Option<u64>) -> NbtError {
+        if self.0.path.is_empty() && self.0.offset.is_none() {
+            self.0.path = path.clone();
+            self.0.offset = offset;
+        }
+        self
+    }
+}
+
+impl Display for NbtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.kind)?;
+        let path = self.0.path.to_string();
+        if !path.is_empty() {
+            write!(f, " at {path}")?;
+        }
+        if let Some(offset) = self.0.offset {
+            write!(f, " (byte {offset})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NbtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&self.0.kind)
+    }
+}
+
+impl serde::de::Error for NbtError {
+    fn custom<T: Display>(msg: T) -> Self {
+        NbtError::custom(msg.to_string())
+    }
+}
+
+impl From<NbtErrorKind> for NbtError {
+    fn from(kind: NbtErrorKind) -> Self {
+        NbtError(Box::new(ErrorInner {
+            kind,
+            path: NbtPath::new(),
+            offset: None
+        }))
+    }
+}
+
+impl From<TryFromPrimitiveError<TagId>> for NbtError {
+    fn from(value: TryFromPrimitiveError<TagId>) -> Self {
+        NbtErrorKind::from(value).into()
+    }
+}
+
+impl From<Utf8Error> for NbtError {
+    fn from(value: Utf8Error) -> Self {
+        NbtErrorKind::from(value).into()
+    }
+}
+
+impl From<io::Error> for NbtError {
+    fn from(value: io::Error) -> Self {
+        NbtErrorKind::from(value).into()
+    }
+}
+
 pub type Result<T> = std::result::Result<T, NbtError>;
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use crate::nbt::{visit_nbt, NbtCompound, NbtErrorKind, NbtTreeVisitor, NbtVisitor, Result};
+
+/// How a standalone NBT file (`level.dat`, player data, litematics, structure files) is compressed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NbtCompression {
+    Gzip,
+    Zlib,
+    Uncompressed,
+}
+
+impl NbtCompression {
+    /// Works out the compression from the first two bytes of a file. Uncompressed NBT always starts
+    /// with a compound tag, so anything that isn't gzip or zlib has to start with `0x0A`.
+    pub fn detect(header: &[u8]) -> Option<NbtCompression> {
+        match header {
+            [0x1f, 0x8b, ..] => Some(NbtCompression::Gzip),
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => Some(NbtCompression::Zlib),
+            [0x0a, ..] => Some(NbtCompression::Uncompressed),
+            _ => None
+        }
+    }
+}
+
+/// [`visit_nbt`], for streams that may or may not be compressed. The compression is sniffed from the
+/// first couple of bytes, and anything unrecognised is an error rather than garbage tags.
+pub fn visit_nbt_auto<R: Read, V: NbtVisitor>(mut reader: R, visitor: &mut V) -> Result<()> {
+    let mut header = [0u8; 2];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..])? {
+            0 => break,
+            read => filled += read
+        }
+    }
+
+    let header = &header[..filled];
+    let Some(compression) = NbtCompression::detect(header) else {
+        return Err(NbtErrorKind::UnknownCompression(header.to_vec()).into());
+    };
+
+    let mut reader = header.chain(reader);
+    match compression {
+        NbtCompression::Gzip => visit_nbt(&mut GzDecoder::new(reader), visitor),
+        NbtCompression::Zlib => visit_nbt(&mut ZlibDecoder::new(reader), visitor),
+        NbtCompression::Uncompressed => visit_nbt(&mut reader, visitor),
+    }
+}
+
+/// Reads a whole NBT file into a tree, whatever its compression
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<NbtCompound> {
+    let file = BufReader::new(File::open(path)?);
+    let mut visitor = NbtTreeVisitor::new();
+    visit_nbt_auto(file, &mut visitor)?;
+    Ok(visitor.into_root())
+}
@@ -1,20 +1,35 @@
 //! A lightweight NBT parser designed for correctness and performance. Specifically, it uses the
 //! `java_string` crate to handle the invalid UTF code points that java strings can have (only 
 //! really applicable if the world contains save-state books), and provides a streaming visitor api
-//! to allow for not holding the entire chunk in memory if not needed. 
-
+//! to allow for not holding the entire chunk in memory if not needed. [`NbtWriter`] goes the other
+//! way, streaming tags back out with the same modified UTF-8 handling. For when it's more
+//! convenient to have the whole thing in memory, [`NbtCompound::read`] builds an owned tree, and the
+//! [`snbt`] module converts to and from the stringified format used by commands, and
+//! [`from_reader`] deserializes straight into serde types. Reading is bounded
+//! by [`NbtLimits`], so corrupt input produces an error rather than a huge allocation or a stack
+//! overflow.
+
+mod de;
 mod error;
-
-pub use error::{NbtError, Result};
-
-use std::io::Read;
-use byteorder::{BigEndian, ReadBytesExt};
+mod file;
+mod reader;
+pub mod snbt;
+mod tree;
+mod writer;
+
+pub use de::{from_reader, from_reader_with, NbtDeserializer};
+pub use error::{NbtError, NbtErrorKind, Result};
+pub use file::{read_file, visit_nbt_auto, NbtCompression};
+pub use reader::{visit_nbt, visit_nbt_with, NbtLimits};
+pub use tree::{NbtCompound, NbtList, NbtListElementMut, NbtTag, NbtTreeVisitor};
+pub use writer::NbtWriter;
+
+use std::fmt::{Display, Formatter};
 use java_string::{JavaString};
 use num_enum::{TryFromPrimitive};
 use crate::nbt::NbtPathElement::{Element, Index};
-use crate::util::{cast_byte_buf_to_signed, read_i32_array, read_i64_array};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LeafTag {
     Byte(i8),
     Short(i16),
@@ -27,18 +42,67 @@ pub enum LeafTag {
     IntArray(Vec<i32>),
     LongArray(Vec<i64>),
 }
+impl LeafTag {
+    pub fn tag_id(&self) -> TagId {
+        match self {
+            LeafTag::Byte(_) => TagId::Byte,
+            LeafTag::Short(_) => TagId::Short,
+            LeafTag::Int(_) => TagId::Int,
+            LeafTag::Long(_) => TagId::Long,
+            LeafTag::Float(_) => TagId::Float,
+            LeafTag::Double(_) => TagId::Double,
+            LeafTag::ByteArray(_) => TagId::ByteArray,
+            LeafTag::String(_) => TagId::String,
+            LeafTag::IntArray(_) => TagId::IntArray,
+            LeafTag::LongArray(_) => TagId::LongArray,
+        }
+    }
+}
 
+/// Returned from [`NbtVisitor::enter_tag`] to decide whether a tag (and everything under it) gets
+/// visited
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Visit {
+    Continue,
+    Skip,
+}
+
+/// Callbacks for [`visit_nbt`]. Only `visit_leaf` is required, the structural hooks are there for
+/// visitors that care about where compounds and lists start and end (including empty ones). The
+/// path passed to the hooks points at the compound or list itself.
 pub trait NbtVisitor {
     fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> Result<()>;
+
+    /// Called before every tag is read. Returning [`Visit::Skip`] discards the tag's bytes without
+    /// decoding them, so none of the other callbacks fire for it or anything nested inside.
+    fn enter_tag(&mut self, _tag_id: TagId, _path: &NbtPath) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn enter_compound(&mut self, _path: &NbtPath) -> Result<()> {
+        Ok(())
+    }
+
+    fn exit_compound(&mut self, _path: &NbtPath) -> Result<()> {
+        Ok(())
+    }
+
+    fn enter_list(&mut self, _elem_type: TagId, _len: usize, _path: &NbtPath) -> Result<()> {
+        Ok(())
+    }
+
+    fn exit_list(&mut self, _path: &NbtPath) -> Result<()> {
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum NbtPathElement {
     Element(JavaString),
     Index(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct NbtPath(Vec<NbtPathElement>);
 impl NbtPath {
     fn new() -> Self {
@@ -54,20 +118,44 @@ impl NbtPath {
     }
 
     pub fn peek(&self,) -> Option<&NbtPathElement> {
-        self.0.get(self.len() - 1)
+        self.0.last()
     }
 
     pub fn peek_back(&self, back: usize) -> Option<&NbtPathElement> {
-        self.0.get(self.len() - (1 + back))
+        self.0.get(self.len().checked_sub(1 + back)?)
     }
 
     pub fn get(&self, index: usize) -> Option<&NbtPathElement> {
         self.0.get(index)
     }
 
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Renders the path the way you'd write it in a `/data` command, e.g. `Level.Sections[3].Blocks`.
+/// The root compound's name is left out.
+impl Display for NbtPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        for element in self.0.iter().skip(1) {
+            match element {
+                Element(name) => {
+                    if !out.is_empty() {
+                        out.push('.');
+                    }
+                    snbt::write_path_key(&mut out, name);
+                }
+                Index(index) => out.push_str(&format!("[{index}]")),
+            }
+        }
+        f.write_str(&out)
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, TryFromPrimitive)]
@@ -88,90 +176,23 @@ pub enum TagId {
     LongArray = 0xC,
 }
 
-pub fn visit_nbt<R: Read, V: NbtVisitor>(reader: &mut R, visitor: &mut V) -> Result<()> {
-    let root_id: TagId = reader.read_u8()?.try_into()?;
-    if root_id != TagId::Compound {
-        return Err(NbtError::InvalidNbtRoot(root_id));
-    }
-
-    let mut curr_path = NbtPath::new();
-
-    let root_name = read_string(reader)?;
-    curr_path.push(Element(root_name));
-
-    visit_tag_body(reader, visitor, root_id, &mut curr_path)?;
-
-    Ok(())
+/// The binary encodings of NBT. Java edition's files are [`NbtFlavor::Java`], the protocol since
+/// 1.20.2 leaves out the root compound's name ([`NbtFlavor::Network`]), and bedrock's level.dat and
+/// structure files are [`NbtFlavor::LittleEndian`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NbtFlavor {
+    #[default]
+    Java,
+    Network,
+    LittleEndian,
 }
 
-fn visit_tag_body<R: Read, V: NbtVisitor>(reader: &mut R, visitor: &mut V, tag_id: TagId, curr_path: &mut NbtPath) -> Result<()> {
-    match tag_id {
-        TagId::Byte => {
-            visitor.visit_leaf(LeafTag::Byte(reader.read_i8()?), curr_path)?;
-        }
-        TagId::Short => {
-            visitor.visit_leaf(LeafTag::Short(reader.read_i16::<BigEndian>()?), curr_path)?;
-        }
-        TagId::Int => {
-            visitor.visit_leaf(LeafTag::Int(reader.read_i32::<BigEndian>()?), curr_path)?;
-        }
-        TagId::Long => {
-            visitor.visit_leaf(LeafTag::Long(reader.read_i64::<BigEndian>()?), curr_path)?;
-        }
-        TagId::Float => {
-            visitor.visit_leaf(LeafTag::Float(reader.read_f32::<BigEndian>()?), curr_path)?;
-        }
-        TagId::Double => {
-            visitor.visit_leaf(LeafTag::Double(reader.read_f64::<BigEndian>()?), curr_path)?;
-        }
-        TagId::ByteArray => {
-            let len = reader.read_i32::<BigEndian>()? as usize;
-            let mut bytes = vec![0; len];
-            reader.read_exact(&mut bytes)?;
-            visitor.visit_leaf(LeafTag::ByteArray(cast_byte_buf_to_signed(bytes)), curr_path)?;
-        }
-        TagId::String => {
-            visitor.visit_leaf(LeafTag::String(read_string(reader)?), curr_path)?;
-        }
-        TagId::List => {
-            let tag_id = reader.read_u8()?.try_into()?;
-            let len = reader.read_i32::<BigEndian>()? as usize;
-
-            for i in 0..len {
-                curr_path.push(Index(i));
-                visit_tag_body(reader, visitor, tag_id, curr_path)?;
-                curr_path.pop();
-            }
-        }
-        TagId::Compound => {
-            let mut tag_id = reader.read_u8()?.try_into()?;
-            while tag_id != TagId::End {
-                let name = read_string(reader)?;
-                curr_path.push(Element(name));
-                visit_tag_body(reader, visitor, tag_id, curr_path)?;
-                curr_path.pop();
-                tag_id = reader.read_u8()?.try_into()?;
-            }
-        }
-        TagId::IntArray => {
-            let len = reader.read_i32::<BigEndian>()? as usize;
-            visitor.visit_leaf(LeafTag::IntArray(read_i32_array(reader, len)?), curr_path)?;
-        }
-        TagId::LongArray => {
-            let len = reader.read_i32::<BigEndian>()? as usize;
-            visitor.visit_leaf(LeafTag::LongArray(read_i64_array(reader, len)?), curr_path)?;
-        }
-        TagId::End => return Err(NbtError::InvalidNbtEndTag)
+impl NbtFlavor {
+    pub fn is_little_endian(self) -> bool {
+        self == NbtFlavor::LittleEndian
     }
 
-    Ok(())
-}
-
-#[inline]
-fn read_string<R: Read>(reader: &mut R) -> Result<JavaString> {
-    let len = reader.read_u16::<BigEndian>()? as usize;
-    let mut bytes = vec![0; len];
-    reader.read_exact(&mut bytes)?;
-
-    Ok(JavaString::from_modified_utf8(bytes)?)
+    pub fn has_root_name(self) -> bool {
+        self != NbtFlavor::Network
+    }
 }
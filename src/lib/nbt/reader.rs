@@ -0,0 +1,366 @@
+use std::io;
+use std::io::Read;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use java_string::JavaString;
+use crate::nbt::{LeafTag, NbtError, NbtErrorKind, NbtFlavor, NbtPath, NbtVisitor, Result, TagId, Visit};
+use crate::nbt::NbtPathElement::{Element, Index};
+use crate::util::{cast_byte_buf_to_signed, read_bytes, read_i32_array, read_i64_array};
+
+/// Limits applied while reading NBT, so that a corrupted (or malicious) file errors out instead of
+/// allocating gigabytes or recursing until the stack runs out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NbtLimits {
+    /// Maximum nesting of compounds and lists, vanilla uses 512
+    pub max_depth: usize,
+    /// Maximum number of elements in a single array or list
+    pub max_array_len: usize,
+    /// Maximum number of bytes read from the (decompressed) stream
+    pub max_total_bytes: u64,
+}
+
+impl NbtLimits {
+    pub const UNLIMITED: NbtLimits = NbtLimits {
+        max_depth: usize::MAX,
+        max_array_len: usize::MAX,
+        max_total_bytes: u64::MAX,
+    };
+}
+
+impl Default for NbtLimits {
+    fn default() -> Self {
+        NbtLimits {
+            max_depth: 512,
+            max_array_len: 1 << 24,
+            max_total_bytes: 1 << 28,
+        }
+    }
+}
+
+/// Reads an NBT stream, handing every tag to `visitor`. Errors (including ones returned by the
+/// visitor) have the path of the tag being read and the offset into the stream attached.
+pub fn visit_nbt<R: Read, V: NbtVisitor>(reader: &mut R, visitor: &mut V) -> Result<()> {
+    visit_nbt_with(reader, visitor, NbtFlavor::Java, NbtLimits::default())
+}
+
+/// [`visit_nbt`], for other flavors of NBT or with different limits. The root compound of
+/// [`NbtFlavor::Network`] NBT gets an empty name in the visited paths.
+pub fn visit_nbt_with<R: Read, V: NbtVisitor>(reader: &mut R, visitor: &mut V, flavor: NbtFlavor, limits: NbtLimits) -> Result<()> {
+    let mut reader = TagReader::new(reader, flavor, limits);
+    let mut curr_path = NbtPath::new();
+
+    reader.visit_root(visitor, &mut curr_path)
+        .map_err(|err| err.with_location(&curr_path, Some(reader.offset)))
+}
+
+/// Wraps the underlying reader, keeping track of how far into the stream we are and how deep in the
+/// tree, and enforcing the limits on both
+pub(crate) struct TagReader<R> {
+    reader: R,
+    pub(crate) offset: u64,
+    depth: usize,
+    flavor: NbtFlavor,
+    limits: NbtLimits,
+}
+
+impl<R: Read> Read for TagReader<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read> TagReader<R> {
+    pub(crate) fn new(reader: R, flavor: NbtFlavor, limits: NbtLimits) -> Self {
+        TagReader {
+            reader,
+            offset: 0,
+            depth: 0,
+            flavor,
+            limits,
+        }
+    }
+
+    fn visit_root<V: NbtVisitor>(&mut self, visitor: &mut V, curr_path: &mut NbtPath) -> Result<()> {
+        let root_name = self.read_root()?;
+        curr_path.push(Element(root_name));
+
+        self.visit_tag_body(visitor, TagId::Compound, curr_path)
+    }
+
+    /// Reads the root compound's tag id and name, up to the start of its body
+    pub(crate) fn read_root(&mut self) -> Result<JavaString> {
+        let root_id: TagId = self.read_u8()?.try_into()?;
+        if root_id != TagId::Compound {
+            return Err(NbtErrorKind::InvalidNbtRoot(root_id).into());
+        }
+
+        if self.flavor.has_root_name() {
+            self.read_string()
+        } else {
+            Ok(JavaString::new())
+        }
+    }
+
+    fn visit_tag_body<V: NbtVisitor>(&mut self, visitor: &mut V, tag_id: TagId, curr_path: &mut NbtPath) -> Result<()> {
+        self.check_total_bytes(0)?;
+        if visitor.enter_tag(tag_id, curr_path)? == Visit::Skip {
+            return self.skip_tag_body(tag_id);
+        }
+
+        match tag_id {
+            TagId::List => {
+                let tag_id = self.read_u8()?.try_into()?;
+                let len = self.read_length(min_tag_size(tag_id))?;
+
+                self.enter()?;
+                visitor.enter_list(tag_id, len, curr_path)?;
+                for i in 0..len {
+                    curr_path.push(Index(i));
+                    self.visit_tag_body(visitor, tag_id, curr_path)?;
+                    curr_path.pop();
+                }
+                visitor.exit_list(curr_path)?;
+                self.exit();
+            }
+            TagId::Compound => {
+                self.enter()?;
+                visitor.enter_compound(curr_path)?;
+                let mut tag_id = self.read_u8()?.try_into()?;
+                while tag_id != TagId::End {
+                    let name = self.read_string()?;
+                    curr_path.push(Element(name));
+                    self.visit_tag_body(visitor, tag_id, curr_path)?;
+                    curr_path.pop();
+                    tag_id = self.read_u8()?.try_into()?;
+                }
+                visitor.exit_compound(curr_path)?;
+                self.exit();
+            }
+            _ => {
+                let leaf = self.read_leaf(tag_id)?;
+                visitor.visit_leaf(leaf, curr_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Kept out of `visit_tag_body` so the recursive frames stay small
+    #[inline(never)]
+    pub(crate) fn read_leaf(&mut self, tag_id: TagId) -> Result<LeafTag> {
+        Ok(match tag_id {
+            TagId::Byte => LeafTag::Byte(self.read_i8()?),
+            TagId::Short => LeafTag::Short(self.read_short()?),
+            TagId::Int => LeafTag::Int(self.read_int()?),
+            TagId::Long => LeafTag::Long(self.read_long()?),
+            TagId::Float => LeafTag::Float(self.read_float()?),
+            TagId::Double => LeafTag::Double(self.read_double()?),
+            TagId::ByteArray => {
+                let len = self.read_length(1)?;
+                LeafTag::ByteArray(cast_byte_buf_to_signed(read_bytes(self, len)?))
+            }
+            TagId::String => LeafTag::String(self.read_string()?),
+            TagId::IntArray => {
+                let len = self.read_length(4)?;
+                LeafTag::IntArray(self.read_int_array(len)?)
+            }
+            TagId::LongArray => {
+                let len = self.read_length(8)?;
+                LeafTag::LongArray(self.read_long_array(len)?)
+            }
+            TagId::List | TagId::Compound => return Err(NbtError::custom("read_leaf called on a non-leaf tag")),
+            TagId::End => return Err(NbtErrorKind::InvalidNbtEndTag.into())
+        })
+    }
+
+    pub(crate) fn skip_tag_body(&mut self, tag_id: TagId) -> Result<()> {
+        match tag_id {
+            TagId::Byte => self.skip_bytes(1)?,
+            TagId::Short => self.skip_bytes(2)?,
+            TagId::Int | TagId::Float => self.skip_bytes(4)?,
+            TagId::Long | TagId::Double => self.skip_bytes(8)?,
+            TagId::ByteArray => {
+                let len = self.read_length(1)?;
+                self.skip_bytes(len as u64)?;
+            }
+            TagId::String => {
+                let len = self.read_string_length()? as u64;
+                self.skip_bytes(len)?;
+            }
+            TagId::List => {
+                let tag_id = self.read_u8()?.try_into()?;
+                let len = self.read_length(min_tag_size(tag_id))?;
+                // Lists of numbers can be skipped in one go
+                let elem_size = match tag_id {
+                    TagId::Byte => Some(1),
+                    TagId::Short => Some(2),
+                    TagId::Int | TagId::Float => Some(4),
+                    TagId::Long | TagId::Double => Some(8),
+                    _ => None
+                };
+                if let Some(elem_size) = elem_size {
+                    self.skip_bytes(len as u64 * elem_size)?;
+                } else {
+                    self.enter()?;
+                    for _ in 0..len {
+                        self.skip_tag_body(tag_id)?;
+                    }
+                    self.exit();
+                }
+            }
+            TagId::Compound => {
+                self.enter()?;
+                let mut tag_id = self.read_u8()?.try_into()?;
+                while tag_id != TagId::End {
+                    let name_len = self.read_string_length()? as u64;
+                    self.skip_bytes(name_len)?;
+                    self.skip_tag_body(tag_id)?;
+                    tag_id = self.read_u8()?.try_into()?;
+                }
+                self.exit();
+            }
+            TagId::IntArray => {
+                let len = self.read_length(4)?;
+                self.skip_bytes(len as u64 * 4)?;
+            }
+            TagId::LongArray => {
+                let len = self.read_length(8)?;
+                self.skip_bytes(len as u64 * 8)?;
+            }
+            TagId::End => return Err(NbtErrorKind::InvalidNbtEndTag.into())
+        }
+
+        Ok(())
+    }
+
+    /// Reads the length of an array or list, and checks it against the limits. `elem_size` is the
+    /// minimum number of bytes each element takes up, so a length that can't possibly fit in the
+    /// remaining byte budget is rejected before anything gets allocated.
+    pub(crate) fn read_length(&mut self, elem_size: u64) -> Result<usize> {
+        let len = self.read_int()?;
+        if len < 0 {
+            return Err(NbtErrorKind::NegativeLength(len).into());
+        }
+        let len = len as usize;
+        if len > self.limits.max_array_len {
+            return Err(NbtErrorKind::ArrayLengthLimitExceeded { len, limit: self.limits.max_array_len }.into());
+        }
+        self.check_total_bytes(len as u64 * elem_size)?;
+        Ok(len)
+    }
+
+    pub(crate) fn read_short(&mut self) -> io::Result<i16> {
+        if self.flavor.is_little_endian() {
+            self.read_i16::<LittleEndian>()
+        } else {
+            self.read_i16::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_int(&mut self) -> io::Result<i32> {
+        if self.flavor.is_little_endian() {
+            self.read_i32::<LittleEndian>()
+        } else {
+            self.read_i32::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_long(&mut self) -> io::Result<i64> {
+        if self.flavor.is_little_endian() {
+            self.read_i64::<LittleEndian>()
+        } else {
+            self.read_i64::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_float(&mut self) -> io::Result<f32> {
+        if self.flavor.is_little_endian() {
+            self.read_f32::<LittleEndian>()
+        } else {
+            self.read_f32::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_double(&mut self) -> io::Result<f64> {
+        if self.flavor.is_little_endian() {
+            self.read_f64::<LittleEndian>()
+        } else {
+            self.read_f64::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_int_array(&mut self, len: usize) -> io::Result<Vec<i32>> {
+        if self.flavor.is_little_endian() {
+            read_i32_array::<LittleEndian, _>(self, len)
+        } else {
+            read_i32_array::<BigEndian, _>(self, len)
+        }
+    }
+
+    pub(crate) fn read_long_array(&mut self, len: usize) -> io::Result<Vec<i64>> {
+        if self.flavor.is_little_endian() {
+            read_i64_array::<LittleEndian, _>(self, len)
+        } else {
+            read_i64_array::<BigEndian, _>(self, len)
+        }
+    }
+
+    fn read_string_length(&mut self) -> io::Result<u16> {
+        if self.flavor.is_little_endian() {
+            self.read_u16::<LittleEndian>()
+        } else {
+            self.read_u16::<BigEndian>()
+        }
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<JavaString> {
+        let len = self.read_string_length()? as usize;
+        let mut bytes = vec![0; len];
+        self.read_exact(&mut bytes)?;
+
+        Ok(JavaString::from_modified_utf8(bytes)?)
+    }
+
+    /// Errors if reading another `needed` bytes would go over the byte limit
+    pub(crate) fn check_total_bytes(&self, needed: u64) -> Result<()> {
+        if self.offset.saturating_add(needed) > self.limits.max_total_bytes {
+            return Err(NbtErrorKind::ByteLimitExceeded(self.limits.max_total_bytes).into());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.limits.max_depth {
+            return Err(NbtErrorKind::DepthLimitExceeded(self.limits.max_depth).into());
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn exit(&mut self) {
+        self.depth -= 1;
+    }
+
+    #[inline]
+    fn skip_bytes(&mut self, len: u64) -> Result<()> {
+        let skipped = io::copy(&mut self.by_ref().take(len), &mut io::sink())?;
+        if skipped != len {
+            return Err(NbtErrorKind::IoError(io::ErrorKind::UnexpectedEof.into()).into());
+        }
+        Ok(())
+    }
+}
+
+/// The fewest bytes a tag of this type can take up in a list
+pub(crate) fn min_tag_size(tag_id: TagId) -> u64 {
+    match tag_id {
+        TagId::End => 0,
+        TagId::Byte | TagId::Compound => 1,
+        TagId::Short | TagId::String => 2,
+        TagId::Int | TagId::Float | TagId::ByteArray | TagId::IntArray | TagId::LongArray => 4,
+        TagId::List => 5,
+        TagId::Long | TagId::Double => 8,
+    }
+}
@@ -0,0 +1,436 @@
+//! Stringified NBT, the format used by commands and NBTExplorer style tools. Output follows the
+//! compact vanilla style (`{Count:1b,id:"minecraft:stone"}`), with strings always quoted. Anything
+//! that isn't valid unicode in a [`JavaString`] (unpaired surrogates from save-state books) is
+//! written as a `\uXXXX` escape, so printing and parsing back is lossless.
+
+use std::fmt::Write;
+use java_string::{JavaCodePoint, JavaStr, JavaString};
+use crate::nbt::{LeafTag, NbtCompound, NbtError, NbtErrorKind, NbtList, NbtPath, NbtPathElement, NbtTag, NbtVisitor, Result, TagId};
+
+/// Visitor that prints everything it visits as SNBT
+pub struct SnbtPrinter {
+    out: String,
+    // One entry per open compound or list, whether the next value is the first one in it
+    first: Vec<bool>,
+}
+
+impl SnbtPrinter {
+    pub fn new() -> Self {
+        SnbtPrinter {
+            out: String::new(),
+            first: Vec::new(),
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        self.out
+    }
+
+    fn write_prefix(&mut self, path: &NbtPath) {
+        let Some(first) = self.first.last_mut() else {
+            // Root tag
+            return;
+        };
+        if !*first {
+            self.out.push(',');
+        }
+        *first = false;
+        if let Some(NbtPathElement::Element(name)) = path.peek() {
+            write_key(&mut self.out, name);
+            self.out.push(':');
+        }
+    }
+}
+
+impl Default for SnbtPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NbtVisitor for SnbtPrinter {
+    fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> Result<()> {
+        self.write_prefix(path);
+        write_leaf(&mut self.out, &val);
+        Ok(())
+    }
+
+    fn enter_compound(&mut self, path: &NbtPath) -> Result<()> {
+        self.write_prefix(path);
+        self.out.push('{');
+        self.first.push(true);
+        Ok(())
+    }
+
+    fn exit_compound(&mut self, _path: &NbtPath) -> Result<()> {
+        self.out.push('}');
+        self.first.pop();
+        Ok(())
+    }
+
+    fn enter_list(&mut self, _elem_type: TagId, _len: usize, path: &NbtPath) -> Result<()> {
+        self.write_prefix(path);
+        self.out.push('[');
+        self.first.push(true);
+        Ok(())
+    }
+
+    fn exit_list(&mut self, _path: &NbtPath) -> Result<()> {
+        self.out.push(']');
+        self.first.pop();
+        Ok(())
+    }
+}
+
+/// Parses an SNBT compound into a tree
+pub fn parse_snbt(input: &str) -> Result<NbtCompound> {
+    let mut parser = SnbtParser { input, pos: 0 };
+    parser.skip_whitespace();
+    if parser.peek() != Some('{') {
+        return Err(parser.error("Expected a compound"));
+    }
+    let root = parser.parse_compound()?;
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+        return Err(parser.error("Trailing data after the root compound"));
+    }
+    Ok(root)
+}
+
+/// Parses an SNBT compound and hands it to `visitor` as if it had been read with
+/// [`visit_nbt`](super::visit_nbt)
+pub fn visit_snbt<V: NbtVisitor>(input: &str, visitor: &mut V) -> Result<()> {
+    parse_snbt(input)?.visit(visitor)
+}
+
+fn write_leaf(out: &mut String, val: &LeafTag) {
+    match val {
+        LeafTag::Byte(val) => write!(out, "{val}b").unwrap(),
+        LeafTag::Short(val) => write!(out, "{val}s").unwrap(),
+        LeafTag::Int(val) => write!(out, "{val}").unwrap(),
+        LeafTag::Long(val) => write!(out, "{val}L").unwrap(),
+        LeafTag::Float(val) => write_float(out, val, 'f'),
+        LeafTag::Double(val) => write_float(out, val, 'd'),
+        LeafTag::ByteArray(vals) => write_array(out, 'B', vals, "b"),
+        LeafTag::String(val) => write_string(out, val),
+        LeafTag::IntArray(vals) => write_array(out, 'I', vals, ""),
+        LeafTag::LongArray(vals) => write_array(out, 'L', vals, "L"),
+    }
+}
+
+fn write_float<F: std::fmt::Display>(out: &mut String, val: F, suffix: char) {
+    // Infinities are spelled the way java prints them
+    match val.to_string().as_str() {
+        "inf" => out.push_str("Infinity"),
+        "-inf" => out.push_str("-Infinity"),
+        val => out.push_str(val),
+    }
+    out.push(suffix);
+}
+
+fn write_array<T: std::fmt::Display>(out: &mut String, prefix: char, vals: &[T], suffix: &str) {
+    out.push('[');
+    out.push(prefix);
+    out.push(';');
+    for (i, val) in vals.iter().enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        write!(out, "{val}{suffix}").unwrap();
+    }
+    out.push(']');
+}
+
+pub(super) fn write_key(out: &mut String, key: &JavaStr) {
+    if !key.is_empty() && key.chars().all(|c| c.as_char().is_some_and(is_unquoted_char)) {
+        out.push_str(&key.as_str_lossy());
+    } else {
+        write_string(out, key);
+    }
+}
+
+/// Like [`write_key`], but a `.` in a path would read as a separator so those keys get quoted too
+pub(super) fn write_path_key(out: &mut String, key: &JavaStr) {
+    if key.chars().any(|c| c.as_char() == Some('.')) {
+        write_string(out, key);
+    } else {
+        write_key(out, key);
+    }
+}
+
+fn write_string(out: &mut String, val: &JavaStr) {
+    out.push('"');
+    for c in val.chars() {
+        match c.as_char() {
+            Some('"') => out.push_str("\\\""),
+            Some('\\') => out.push_str("\\\\"),
+            Some('\n') => out.push_str("\\n"),
+            Some('\r') => out.push_str("\\r"),
+            Some('\t') => out.push_str("\\t"),
+            Some(c) if !c.is_control() => out.push(c),
+            // Control characters and unpaired surrogates
+            _ => write!(out, "\\u{:04X}", c.as_u32()).unwrap(),
+        }
+    }
+    out.push('"');
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+struct SnbtParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> SnbtParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.next() != Some(expected) {
+            return Err(self.error(&format!("Expected '{expected}'")));
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> NbtError {
+        NbtErrorKind::InvalidSnbt { message: message.to_string(), pos: self.pos }.into()
+    }
+
+    fn parse_value(&mut self) -> Result<NbtTag> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => Ok(NbtTag::Compound(self.parse_compound()?)),
+            Some('[') => self.parse_list_or_array(),
+            Some('"') | Some('\'') => Ok(NbtTag::String(self.parse_quoted_string()?)),
+            Some(_) => {
+                let start = self.pos;
+                let token = self.parse_unquoted();
+                if token.is_empty() {
+                    self.pos = start;
+                    return Err(self.error("Expected a value"));
+                }
+                Ok(parse_unquoted_value(token))
+            }
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn parse_compound(&mut self) -> Result<NbtCompound> {
+        self.expect('{')?;
+        let mut compound = NbtCompound::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.next();
+            return Ok(compound);
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.parse_quoted_string()?,
+                _ => {
+                    let key = self.parse_unquoted();
+                    if key.is_empty() {
+                        return Err(self.error("Expected a key"));
+                    }
+                    key.into()
+                }
+            };
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            compound.insert(key, value);
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(compound),
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_list_or_array(&mut self) -> Result<NbtTag> {
+        self.expect('[')?;
+        let rest = &self.input[self.pos..];
+        let array_type = match rest.get(..2) {
+            Some("B;") => Some(TagId::ByteArray),
+            Some("I;") => Some(TagId::IntArray),
+            Some("L;") => Some(TagId::LongArray),
+            _ => None,
+        };
+        if let Some(array_type) = array_type {
+            self.pos += 2;
+            return self.parse_array(array_type);
+        }
+
+        let mut list = NbtList::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+            return Ok(NbtTag::List(list));
+        }
+        loop {
+            let start = self.pos;
+            let value = self.parse_value()?;
+            if let Err(err) = list.push(value) {
+                self.pos = start;
+                return Err(self.error(&err.to_string()));
+            }
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(NbtTag::List(list)),
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, array_type: TagId) -> Result<NbtTag> {
+        let mut vals: Vec<i64> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.next();
+        } else {
+            loop {
+                let start = self.pos;
+                // Smaller integer types widen into bigger arrays, same as vanilla
+                let val = match (array_type, self.parse_value()?) {
+                    (_, NbtTag::Byte(val)) => val as i64,
+                    (TagId::IntArray | TagId::LongArray, NbtTag::Short(val)) => val as i64,
+                    (TagId::IntArray | TagId::LongArray, NbtTag::Int(val)) => val as i64,
+                    (TagId::LongArray, NbtTag::Long(val)) => val,
+                    (_, tag) => {
+                        self.pos = start;
+                        return Err(self.error(&format!("Can't insert {:?} into {:?}", tag.tag_id(), array_type)));
+                    }
+                };
+                vals.push(val);
+
+                self.skip_whitespace();
+                match self.next() {
+                    Some(',') => continue,
+                    Some(']') => break,
+                    _ => return Err(self.error("Expected ',' or ']'")),
+                }
+            }
+        }
+
+        Ok(match array_type {
+            TagId::ByteArray => NbtTag::ByteArray(vals.into_iter().map(|v| v as i8).collect()),
+            TagId::IntArray => NbtTag::IntArray(vals.into_iter().map(|v| v as i32).collect()),
+            _ => NbtTag::LongArray(vals),
+        })
+    }
+
+    fn parse_unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(is_unquoted_char) {
+            self.next();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn parse_quoted_string(&mut self) -> Result<JavaString> {
+        let Some(quote) = self.next() else {
+            return Err(self.error("Expected a string"));
+        };
+        let mut string = JavaString::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("Unterminated string")),
+                Some(c) if c == quote => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('u') => {
+                        let code_point = self.parse_unicode_escape()?;
+                        string.push_java(code_point);
+                    }
+                    Some(c @ ('\\' | '"' | '\'')) => string.push(c),
+                    _ => return Err(self.error("Invalid escape sequence")),
+                },
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a `\u` escape, combining surrogate pairs into a single code point
+    fn parse_unicode_escape(&mut self) -> Result<JavaCodePoint> {
+        let high = self.parse_hex_u16()?;
+        if (0xD800..0xDC00).contains(&high) && self.input[self.pos..].starts_with("\\u") {
+            let start = self.pos;
+            self.pos += 2;
+            let low = self.parse_hex_u16()?;
+            if (0xDC00..0xE000).contains(&low) {
+                let code_point = 0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00);
+                return Ok(JavaCodePoint::from_u32(code_point).unwrap());
+            }
+            // Not a pair, the second escape gets parsed on its own
+            self.pos = start;
+        }
+        Ok(JavaCodePoint::from_u32(high as u32).unwrap())
+    }
+
+    fn parse_hex_u16(&mut self) -> Result<u16> {
+        let digits = self.input.get(self.pos..self.pos + 4).ok_or_else(|| self.error("Invalid unicode escape"))?;
+        let val = u16::from_str_radix(digits, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(val)
+    }
+}
+
+/// Works out the type of an unquoted token from its suffix, falling back to a string like vanilla
+fn parse_unquoted_value(token: &str) -> NbtTag {
+    match token {
+        "true" => return NbtTag::Byte(1),
+        "false" => return NbtTag::Byte(0),
+        _ => {}
+    }
+
+    let (body, suffix) = token.split_at(token.len() - 1);
+    let parsed = match suffix {
+        "b" | "B" if is_integer(body) => body.parse().ok().map(NbtTag::Byte),
+        "s" | "S" if is_integer(body) => body.parse().ok().map(NbtTag::Short),
+        "l" | "L" if is_integer(body) => body.parse().ok().map(NbtTag::Long),
+        "f" | "F" if is_float(body) => body.parse().ok().map(NbtTag::Float),
+        "d" | "D" if is_float(body) => body.parse().ok().map(NbtTag::Double),
+        _ if is_integer(token) => token.parse().ok().map(NbtTag::Int),
+        _ if is_float(token) && token.contains(['.', 'e', 'E']) => token.parse().ok().map(NbtTag::Double),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| NbtTag::String(token.into()))
+}
+
+fn is_integer(body: &str) -> bool {
+    let digits = body.strip_prefix(['-', '+']).unwrap_or(body);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_float(body: &str) -> bool {
+    let digits = body.strip_prefix(['-', '+']).unwrap_or(body);
+    if digits == "NaN" || digits == "Infinity" {
+        return true;
+    }
+    digits.chars().any(|c| c.is_ascii_digit()) &&
+        digits.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'))
+}
@@ -0,0 +1,584 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::ops::{Deref, Index, IndexMut};
+use java_string::{JavaStr, JavaString};
+use crate::nbt::{visit_nbt_with, LeafTag, NbtError, NbtErrorKind, NbtFlavor, NbtLimits, NbtPath, NbtPathElement, NbtVisitor, NbtWriter, Result, TagId, Visit};
+use crate::nbt::snbt::SnbtPrinter;
+
+/// An owned NBT tag, for when holding (part of) the tree in memory is more convenient than writing
+/// a dedicated visitor
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(JavaString),
+    List(NbtList),
+    Compound(NbtCompound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl NbtTag {
+    pub fn tag_id(&self) -> TagId {
+        match self {
+            NbtTag::Byte(_) => TagId::Byte,
+            NbtTag::Short(_) => TagId::Short,
+            NbtTag::Int(_) => TagId::Int,
+            NbtTag::Long(_) => TagId::Long,
+            NbtTag::Float(_) => TagId::Float,
+            NbtTag::Double(_) => TagId::Double,
+            NbtTag::ByteArray(_) => TagId::ByteArray,
+            NbtTag::String(_) => TagId::String,
+            NbtTag::List(_) => TagId::List,
+            NbtTag::Compound(_) => TagId::Compound,
+            NbtTag::IntArray(_) => TagId::IntArray,
+            NbtTag::LongArray(_) => TagId::LongArray,
+        }
+    }
+
+    pub fn as_byte(&self) -> Option<i8> {
+        if let NbtTag::Byte(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_short(&self) -> Option<i16> {
+        if let NbtTag::Short(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        if let NbtTag::Int(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        if let NbtTag::Long(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        if let NbtTag::Float(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        if let NbtTag::Double(val) = self { Some(*val) } else { None }
+    }
+
+    pub fn as_byte_array(&self) -> Option<&[i8]> {
+        if let NbtTag::ByteArray(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_string(&self) -> Option<&JavaStr> {
+        if let NbtTag::String(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_list(&self) -> Option<&NbtList> {
+        if let NbtTag::List(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut NbtList> {
+        if let NbtTag::List(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_compound(&self) -> Option<&NbtCompound> {
+        if let NbtTag::Compound(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut NbtCompound> {
+        if let NbtTag::Compound(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_int_array(&self) -> Option<&[i32]> {
+        if let NbtTag::IntArray(val) = self { Some(val) } else { None }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        if let NbtTag::LongArray(val) = self { Some(val) } else { None }
+    }
+
+    pub fn write_named<W: Write, S: AsRef<JavaStr>>(&self, writer: &mut NbtWriter<W>, name: S) -> Result<()> {
+        match self {
+            NbtTag::List(list) => {
+                writer.begin_list(name, list.elem_type, list.len())?;
+                list.write_elements(writer)?;
+                writer.end_list()
+            }
+            NbtTag::Compound(compound) => {
+                writer.begin_compound(name)?;
+                compound.write_entries(writer)?;
+                writer.end_compound()
+            }
+            _ => writer.write_leaf(name, &self.to_leaf().unwrap())
+        }
+    }
+
+    pub fn write_element<W: Write>(&self, writer: &mut NbtWriter<W>) -> Result<()> {
+        match self {
+            NbtTag::List(list) => {
+                writer.begin_list_element(list.elem_type, list.len())?;
+                list.write_elements(writer)?;
+                writer.end_list()
+            }
+            NbtTag::Compound(compound) => {
+                writer.begin_compound_element()?;
+                compound.write_entries(writer)?;
+                writer.end_compound()
+            }
+            _ => writer.write_leaf_element(&self.to_leaf().unwrap())
+        }
+    }
+
+    fn visit<V: NbtVisitor>(&self, visitor: &mut V, path: &mut NbtPath) -> Result<()> {
+        if visitor.enter_tag(self.tag_id(), path)? == Visit::Skip {
+            return Ok(());
+        }
+        match self {
+            NbtTag::List(list) => {
+                visitor.enter_list(list.elem_type, list.len(), path)?;
+                for (i, element) in list.iter().enumerate() {
+                    path.push(NbtPathElement::Index(i));
+                    element.visit(visitor, path)?;
+                    path.pop();
+                }
+                visitor.exit_list(path)
+            }
+            NbtTag::Compound(compound) => compound.visit_entries(visitor, path),
+            _ => visitor.visit_leaf(self.to_leaf().unwrap(), path)
+        }
+    }
+
+    fn to_leaf(&self) -> Option<LeafTag> {
+        Some(match self {
+            NbtTag::Byte(val) => LeafTag::Byte(*val),
+            NbtTag::Short(val) => LeafTag::Short(*val),
+            NbtTag::Int(val) => LeafTag::Int(*val),
+            NbtTag::Long(val) => LeafTag::Long(*val),
+            NbtTag::Float(val) => LeafTag::Float(*val),
+            NbtTag::Double(val) => LeafTag::Double(*val),
+            NbtTag::ByteArray(val) => LeafTag::ByteArray(val.clone()),
+            NbtTag::String(val) => LeafTag::String(val.clone()),
+            NbtTag::IntArray(val) => LeafTag::IntArray(val.clone()),
+            NbtTag::LongArray(val) => LeafTag::LongArray(val.clone()),
+            NbtTag::List(_) | NbtTag::Compound(_) => return None,
+        })
+    }
+}
+
+impl From<LeafTag> for NbtTag {
+    fn from(value: LeafTag) -> Self {
+        match value {
+            LeafTag::Byte(val) => NbtTag::Byte(val),
+            LeafTag::Short(val) => NbtTag::Short(val),
+            LeafTag::Int(val) => NbtTag::Int(val),
+            LeafTag::Long(val) => NbtTag::Long(val),
+            LeafTag::Float(val) => NbtTag::Float(val),
+            LeafTag::Double(val) => NbtTag::Double(val),
+            LeafTag::ByteArray(val) => NbtTag::ByteArray(val),
+            LeafTag::String(val) => NbtTag::String(val),
+            LeafTag::IntArray(val) => NbtTag::IntArray(val),
+            LeafTag::LongArray(val) => NbtTag::LongArray(val),
+        }
+    }
+}
+
+macro_rules! impl_nbt_tag_from {
+    ($($from:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$from> for NbtTag {
+                fn from(value: $from) -> Self {
+                    NbtTag::$variant(value)
+                }
+            }
+        )*
+    };
+}
+impl_nbt_tag_from! {
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    Vec<i8> => ByteArray,
+    JavaString => String,
+    NbtList => List,
+    NbtCompound => Compound,
+    Vec<i32> => IntArray,
+    Vec<i64> => LongArray,
+}
+impl From<&str> for NbtTag {
+    fn from(value: &str) -> Self {
+        NbtTag::String(value.into())
+    }
+}
+impl From<&JavaStr> for NbtTag {
+    fn from(value: &JavaStr) -> Self {
+        NbtTag::String(value.into())
+    }
+}
+
+/// A compound tag. Entries are kept in insertion order, so a compound that was read in is written
+/// back out in the same order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NbtCompound(Vec<(JavaString, NbtTag)>);
+
+impl NbtCompound {
+    pub fn new() -> Self {
+        NbtCompound(Vec::new())
+    }
+
+    /// Reads a full NBT stream into a tree, discarding the name of the root compound (which is
+    /// empty for basically every file minecraft writes)
+    pub fn read<R: Read>(reader: &mut R) -> Result<NbtCompound> {
+        Self::read_with(reader, NbtFlavor::Java, NbtLimits::default())
+    }
+
+    pub fn read_with<R: Read>(reader: &mut R, flavor: NbtFlavor, limits: NbtLimits) -> Result<NbtCompound> {
+        let mut visitor = NbtTreeVisitor::new();
+        visit_nbt_with(reader, &mut visitor, flavor, limits)?;
+        Ok(visitor.into_root())
+    }
+
+    pub fn write<W: Write, S: AsRef<JavaStr>>(&self, writer: W, root_name: S) -> Result<W> {
+        self.write_with(writer, root_name, NbtFlavor::Java)
+    }
+
+    /// `root_name` is ignored for [`NbtFlavor::Network`], which doesn't have one
+    pub fn write_with<W: Write, S: AsRef<JavaStr>>(&self, writer: W, root_name: S, flavor: NbtFlavor) -> Result<W> {
+        let mut writer = NbtWriter::with_flavor(writer, flavor);
+        if flavor.has_root_name() {
+            writer.begin_compound(root_name)?;
+        } else {
+            writer.begin_compound_element()?;
+        }
+        self.write_entries(&mut writer)?;
+        writer.end_compound()?;
+        writer.finish()
+    }
+
+    /// Writes each entry of this compound into the currently open compound of `writer`
+    pub fn write_entries<W: Write>(&self, writer: &mut NbtWriter<W>) -> Result<()> {
+        for (name, tag) in &self.0 {
+            tag.write_named(writer, name)?;
+        }
+        Ok(())
+    }
+
+    /// Walks the tree, calling `visitor` the same way [`visit_nbt`](super::visit_nbt) would for the serialized form
+    pub fn visit<V: NbtVisitor>(&self, visitor: &mut V) -> Result<()> {
+        let mut path = NbtPath::new();
+        path.push(NbtPathElement::Element(JavaString::new()));
+        let result = match visitor.enter_tag(TagId::Compound, &path) {
+            Ok(Visit::Skip) => Ok(()),
+            Ok(Visit::Continue) => self.visit_entries(visitor, &mut path),
+            Err(err) => Err(err)
+        };
+        result.map_err(|err| err.with_location(&path, None))
+    }
+
+    fn visit_entries<V: NbtVisitor>(&self, visitor: &mut V, path: &mut NbtPath) -> Result<()> {
+        visitor.enter_compound(path)?;
+        for (name, tag) in &self.0 {
+            path.push(NbtPathElement::Element(name.clone()));
+            tag.visit(visitor, path)?;
+            path.pop();
+        }
+        visitor.exit_compound(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains_key<K: AsRef<JavaStr>>(&self, key: K) -> bool {
+        self.position(key.as_ref()).is_some()
+    }
+
+    pub fn get<K: AsRef<JavaStr>>(&self, key: K) -> Option<&NbtTag> {
+        let index = self.position(key.as_ref())?;
+        Some(&self.0[index].1)
+    }
+
+    pub fn get_mut<K: AsRef<JavaStr>>(&mut self, key: K) -> Option<&mut NbtTag> {
+        let index = self.position(key.as_ref())?;
+        Some(&mut self.0[index].1)
+    }
+
+    /// Inserts a tag, returning the old value if the key was already present. Replacing a value
+    /// keeps its original position.
+    pub fn insert<K: Into<JavaString>, T: Into<NbtTag>>(&mut self, key: K, tag: T) -> Option<NbtTag> {
+        let key = key.into();
+        let tag = tag.into();
+        match self.position(&key) {
+            Some(index) => Some(std::mem::replace(&mut self.0[index].1, tag)),
+            None => {
+                self.0.push((key, tag));
+                None
+            }
+        }
+    }
+
+    pub fn remove<K: AsRef<JavaStr>>(&mut self, key: K) -> Option<NbtTag> {
+        let index = self.position(key.as_ref())?;
+        Some(self.0.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&JavaStr, &NbtTag)> + '_ {
+        self.0.iter().map(|(name, tag)| (name.as_java_str(), tag))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=(&JavaStr, &mut NbtTag)> + '_ {
+        self.0.iter_mut().map(|(name, tag)| (name.as_java_str(), tag))
+    }
+
+    fn position(&self, key: &JavaStr) -> Option<usize> {
+        self.0.iter().position(|(name, _)| name == key)
+    }
+}
+
+/// Formats the compound as SNBT
+impl Display for NbtCompound {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut printer = SnbtPrinter::new();
+        self.visit(&mut printer).map_err(|_| std::fmt::Error)?;
+        f.write_str(&printer.into_string())
+    }
+}
+
+impl<K: AsRef<JavaStr>> Index<K> for NbtCompound {
+    type Output = NbtTag;
+
+    fn index(&self, key: K) -> &Self::Output {
+        let key = key.as_ref();
+        self.get(key).unwrap_or_else(|| panic!("No NBT tag named {key:?}"))
+    }
+}
+
+impl<K: AsRef<JavaStr>> IndexMut<K> for NbtCompound {
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
+        let key = key.as_ref();
+        let Some(index) = self.position(key) else {
+            panic!("No NBT tag named {key:?}");
+        };
+        &mut self.0[index].1
+    }
+}
+
+/// A list tag. All elements of a list share the same type, which is checked on [`NbtList::push`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NbtList {
+    elem_type: TagId,
+    elements: Vec<NbtTag>,
+}
+
+impl NbtList {
+    /// Creates an empty list, the element type is picked up from the first element pushed
+    pub fn new() -> Self {
+        Self::with_type(TagId::End)
+    }
+
+    pub fn with_type(elem_type: TagId) -> Self {
+        NbtList {
+            elem_type,
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn elem_type(&self) -> TagId {
+        self.elem_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&NbtTag> {
+        self.elements.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<NbtListElementMut<'_>> {
+        self.elements.get_mut(index).map(|tag| NbtListElementMut { tag })
+    }
+
+    /// Replaces the element at `index`, returning the old one. The new tag has to be the same type
+    /// as the rest of the list.
+    pub fn set<T: Into<NbtTag>>(&mut self, index: usize, tag: T) -> Result<NbtTag> {
+        let elem_type = self.elem_type;
+        let Some(element) = self.elements.get_mut(index) else {
+            return Err(NbtError::custom(format!("List index {index} out of range for length {}", self.elements.len())));
+        };
+        NbtListElementMut { tag: element }.set_checked(tag.into(), elem_type)
+    }
+
+    pub fn push<T: Into<NbtTag>>(&mut self, tag: T) -> Result<()> {
+        let tag = tag.into();
+        if self.elem_type == TagId::End && self.elements.is_empty() {
+            self.elem_type = tag.tag_id();
+        }
+        if tag.tag_id() != self.elem_type {
+            return Err(NbtErrorKind::ListTypeMismatch { expected: self.elem_type, found: tag.tag_id() }.into());
+        }
+        self.elements.push(tag);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> NbtTag {
+        self.elements.remove(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&NbtTag> + '_ {
+        self.elements.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=NbtListElementMut<'_>> + '_ {
+        self.elements.iter_mut().map(|tag| NbtListElementMut { tag })
+    }
+
+    fn write_elements<W: Write>(&self, writer: &mut NbtWriter<W>) -> Result<()> {
+        for element in &self.elements {
+            element.write_element(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for NbtList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for NbtList {
+    type Output = NbtTag;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.elements[index]
+    }
+}
+
+/// Mutable access to an element of an [`NbtList`]. Compounds and lists can be edited in place, but
+/// replacing the element goes through [`NbtListElementMut::set`] so the list's element type can't
+/// change underneath it.
+pub struct NbtListElementMut<'a> {
+    tag: &'a mut NbtTag,
+}
+
+impl NbtListElementMut<'_> {
+    pub fn as_list_mut(&mut self) -> Option<&mut NbtList> {
+        self.tag.as_list_mut()
+    }
+
+    pub fn as_compound_mut(&mut self) -> Option<&mut NbtCompound> {
+        self.tag.as_compound_mut()
+    }
+
+    /// Replaces the element with a tag of the same type, returning the old one
+    pub fn set<T: Into<NbtTag>>(&mut self, tag: T) -> Result<NbtTag> {
+        let elem_type = self.tag.tag_id();
+        self.set_checked(tag.into(), elem_type)
+    }
+
+    fn set_checked(&mut self, tag: NbtTag, elem_type: TagId) -> Result<NbtTag> {
+        if tag.tag_id() != elem_type {
+            return Err(NbtErrorKind::ListTypeMismatch { expected: elem_type, found: tag.tag_id() }.into());
+        }
+        Ok(std::mem::replace(self.tag, tag))
+    }
+}
+
+impl Deref for NbtListElementMut<'_> {
+    type Target = NbtTag;
+
+    fn deref(&self) -> &Self::Target {
+        self.tag
+    }
+}
+
+/// Stock visitor that collects everything it's handed into an [`NbtCompound`]
+pub struct NbtTreeVisitor {
+    root: NbtCompound,
+    // Compounds and lists that have been entered but not exited yet
+    open: Vec<NbtTag>,
+}
+
+impl NbtTreeVisitor {
+    pub fn new() -> Self {
+        NbtTreeVisitor {
+            root: NbtCompound::new(),
+            open: Vec::new(),
+        }
+    }
+
+    pub fn into_root(self) -> NbtCompound {
+        self.root
+    }
+
+    fn attach(&mut self, tag: NbtTag, path: &NbtPath) -> Result<()> {
+        match self.open.last_mut() {
+            None => {
+                let NbtTag::Compound(root) = tag else {
+                    return Err(NbtErrorKind::InvalidNbtRoot(tag.tag_id()).into());
+                };
+                self.root = root;
+            }
+            Some(NbtTag::Compound(compound)) => {
+                let Some(NbtPathElement::Element(name)) = path.peek() else {
+                    return Err(NbtError::custom("Expected a compound key in the NBT path"));
+                };
+                compound.insert(name.clone(), tag);
+            }
+            Some(NbtTag::List(list)) => list.push(tag)?,
+            Some(_) => unreachable!("only compounds and lists are left open"),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, path: &NbtPath) -> Result<()> {
+        let Some(tag) = self.open.pop() else {
+            return Err(NbtError::custom("Unbalanced NBT tree visitor calls"));
+        };
+        self.attach(tag, path)
+    }
+}
+
+impl Default for NbtTreeVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NbtVisitor for NbtTreeVisitor {
+    fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> Result<()> {
+        self.attach(val.into(), path)
+    }
+
+    fn enter_compound(&mut self, _path: &NbtPath) -> Result<()> {
+        self.open.push(NbtTag::Compound(NbtCompound::new()));
+        Ok(())
+    }
+
+    fn exit_compound(&mut self, path: &NbtPath) -> Result<()> {
+        self.close(path)
+    }
+
+    fn enter_list(&mut self, elem_type: TagId, len: usize, _path: &NbtPath) -> Result<()> {
+        let mut list = NbtList::with_type(elem_type);
+        // The length comes straight from the file, so don't trust it too far
+        list.elements.reserve(len.min(1024));
+        self.open.push(NbtTag::List(list));
+        Ok(())
+    }
+
+    fn exit_list(&mut self, path: &NbtPath) -> Result<()> {
+        self.close(path)
+    }
+}
@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+use std::io::Write;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use java_string::JavaStr;
+use crate::nbt::{LeafTag, NbtError, NbtErrorKind, NbtFlavor, Result, TagId};
+use crate::util::{cast_byte_slice_to_unsigned, write_i32_array, write_i64_array};
+
+enum WriterScope {
+    Compound,
+    List { elem_type: TagId, remaining: usize },
+}
+
+/// Streaming NBT writer, the counterpart to [`visit_nbt`](super::visit_nbt). Tags inside a compound
+/// are written with the named methods (`begin_compound`, `begin_list`, `write_leaf`), list elements
+/// with the `_element` variants. The writer keeps track of the open compounds and lists, and errors
+/// out instead of producing NBT that minecraft wouldn't be able to read back.
+pub struct NbtWriter<W: Write> {
+    writer: W,
+    flavor: NbtFlavor,
+    scopes: Vec<WriterScope>,
+}
+
+impl<W: Write> NbtWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_flavor(writer, NbtFlavor::Java)
+    }
+
+    /// The root compound of [`NbtFlavor::Network`] NBT has no name, so it has to be started with
+    /// [`begin_compound_element`](Self::begin_compound_element) instead of `begin_compound`.
+    pub fn with_flavor(writer: W, flavor: NbtFlavor) -> Self {
+        NbtWriter {
+            writer,
+            flavor,
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn begin_compound<S: AsRef<JavaStr>>(&mut self, name: S) -> Result<()> {
+        self.write_header(TagId::Compound, Some(name.as_ref()))?;
+        self.scopes.push(WriterScope::Compound);
+        Ok(())
+    }
+
+    pub fn begin_compound_element(&mut self) -> Result<()> {
+        self.write_header(TagId::Compound, None)?;
+        self.scopes.push(WriterScope::Compound);
+        Ok(())
+    }
+
+    pub fn end_compound(&mut self) -> Result<()> {
+        let Some(WriterScope::Compound) = self.scopes.last() else {
+            return Err(NbtErrorKind::InvalidWriterState("end_compound called outside of a compound").into());
+        };
+        self.scopes.pop();
+        self.writer.write_u8(TagId::End as u8)?;
+        Ok(())
+    }
+
+    pub fn begin_list<S: AsRef<JavaStr>>(&mut self, name: S, elem_type: TagId, len: usize) -> Result<()> {
+        check_list_header(elem_type, len)?;
+        self.write_header(TagId::List, Some(name.as_ref()))?;
+        self.write_list_header(elem_type, len)
+    }
+
+    pub fn begin_list_element(&mut self, elem_type: TagId, len: usize) -> Result<()> {
+        check_list_header(elem_type, len)?;
+        self.write_header(TagId::List, None)?;
+        self.write_list_header(elem_type, len)
+    }
+
+    pub fn end_list(&mut self) -> Result<()> {
+        let Some(WriterScope::List { remaining, .. }) = self.scopes.last() else {
+            return Err(NbtErrorKind::InvalidWriterState("end_list called outside of a list").into());
+        };
+        if *remaining != 0 {
+            return Err(NbtErrorKind::InvalidWriterState("end_list called before all list elements were written").into());
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    pub fn write_leaf<S: AsRef<JavaStr>>(&mut self, name: S, val: &LeafTag) -> Result<()> {
+        check_leaf(val)?;
+        self.write_header(val.tag_id(), Some(name.as_ref()))?;
+        self.write_leaf_body(val)
+    }
+
+    pub fn write_leaf_element(&mut self, val: &LeafTag) -> Result<()> {
+        check_leaf(val)?;
+        self.write_header(val.tag_id(), None)?;
+        self.write_leaf_body(val)
+    }
+
+    /// Checks that every compound and list has been closed, and hands back the underlying writer
+    pub fn finish(self) -> Result<W> {
+        if !self.scopes.is_empty() {
+            return Err(NbtErrorKind::InvalidWriterState("finish called with unclosed compounds or lists").into());
+        }
+        Ok(self.writer)
+    }
+
+    /// Everything that can fail is checked before the first byte is written, so an error never
+    /// leaves half a tag behind
+    fn write_header(&mut self, tag_id: TagId, name: Option<&JavaStr>) -> Result<()> {
+        let name = name.map(modified_utf8).transpose()?;
+        match self.scopes.last_mut() {
+            None => {
+                if tag_id != TagId::Compound {
+                    return Err(NbtErrorKind::InvalidNbtRoot(tag_id).into());
+                }
+                match (&name, self.flavor.has_root_name()) {
+                    (None, true) => return Err(NbtErrorKind::InvalidWriterState("root compound must be named").into()),
+                    (Some(_), false) => return Err(NbtErrorKind::InvalidWriterState("network NBT root compound can't be named").into()),
+                    _ => {}
+                }
+                self.writer.write_u8(tag_id as u8)?;
+                if let Some(name) = name {
+                    self.write_string(&name)?;
+                }
+            }
+            Some(WriterScope::Compound) => {
+                let Some(name) = name else {
+                    return Err(NbtErrorKind::InvalidWriterState("compound entries must be named").into());
+                };
+                self.writer.write_u8(tag_id as u8)?;
+                self.write_string(&name)?;
+            }
+            Some(WriterScope::List { elem_type, remaining }) => {
+                if name.is_some() {
+                    return Err(NbtErrorKind::InvalidWriterState("list elements can't be named").into());
+                }
+                if *elem_type != tag_id {
+                    return Err(NbtErrorKind::ListTypeMismatch { expected: *elem_type, found: tag_id }.into());
+                }
+                if *remaining == 0 {
+                    return Err(NbtErrorKind::InvalidWriterState("more elements written than the list length").into());
+                }
+                *remaining -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_list_header(&mut self, elem_type: TagId, len: usize) -> Result<()> {
+        self.writer.write_u8(elem_type as u8)?;
+        self.write_length(len)?;
+        self.scopes.push(WriterScope::List { elem_type, remaining: len });
+        Ok(())
+    }
+
+    fn write_leaf_body(&mut self, val: &LeafTag) -> Result<()> {
+        if self.flavor.is_little_endian() {
+            write_leaf_body::<LittleEndian, _>(&mut self.writer, val)
+        } else {
+            write_leaf_body::<BigEndian, _>(&mut self.writer, val)
+        }
+    }
+
+    fn write_length(&mut self, len: usize) -> Result<()> {
+        if self.flavor.is_little_endian() {
+            write_length::<LittleEndian, _>(&mut self.writer, len)
+        } else {
+            write_length::<BigEndian, _>(&mut self.writer, len)
+        }
+    }
+
+    fn write_string(&mut self, val: &[u8]) -> Result<()> {
+        if self.flavor.is_little_endian() {
+            write_string::<LittleEndian, _>(&mut self.writer, val)
+        } else {
+            write_string::<BigEndian, _>(&mut self.writer, val)
+        }
+    }
+}
+
+fn write_leaf_body<B: ByteOrder, W: Write>(writer: &mut W, val: &LeafTag) -> Result<()> {
+    match val {
+        LeafTag::Byte(val) => writer.write_i8(*val)?,
+        LeafTag::Short(val) => writer.write_i16::<B>(*val)?,
+        LeafTag::Int(val) => writer.write_i32::<B>(*val)?,
+        LeafTag::Long(val) => writer.write_i64::<B>(*val)?,
+        LeafTag::Float(val) => writer.write_f32::<B>(*val)?,
+        LeafTag::Double(val) => writer.write_f64::<B>(*val)?,
+        LeafTag::ByteArray(vals) => {
+            write_length::<B, _>(writer, vals.len())?;
+            writer.write_all(cast_byte_slice_to_unsigned(vals))?;
+        }
+        LeafTag::String(val) => write_string::<B, _>(writer, &modified_utf8(val)?)?,
+        LeafTag::IntArray(vals) => {
+            write_length::<B, _>(writer, vals.len())?;
+            write_i32_array::<B, _>(writer, vals)?;
+        }
+        LeafTag::LongArray(vals) => {
+            write_length::<B, _>(writer, vals.len())?;
+            write_i64_array::<B, _>(writer, vals)?;
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn write_length<B: ByteOrder, W: Write>(writer: &mut W, len: usize) -> Result<()> {
+    let len = i32::try_from(len).map_err(|_| NbtError::from(NbtErrorKind::LengthOutOfRange(len)))?;
+    writer.write_i32::<B>(len)?;
+    Ok(())
+}
+
+/// Only takes strings that have been through [`modified_utf8`], so the length fits
+#[inline]
+fn write_string<B: ByteOrder, W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    writer.write_u16::<B>(bytes.len() as u16)?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+fn modified_utf8(val: &JavaStr) -> Result<Cow<'_, [u8]>> {
+    let bytes = val.to_modified_utf8();
+    if bytes.len() > u16::MAX as usize {
+        return Err(NbtErrorKind::StringTooLong(bytes.len()).into());
+    }
+    Ok(bytes)
+}
+
+fn check_leaf(val: &LeafTag) -> Result<()> {
+    let len = match val {
+        LeafTag::String(val) => return modified_utf8(val).map(|_| ()),
+        LeafTag::ByteArray(vals) => vals.len(),
+        LeafTag::IntArray(vals) => vals.len(),
+        LeafTag::LongArray(vals) => vals.len(),
+        _ => return Ok(())
+    };
+    check_length(len)
+}
+
+fn check_list_header(elem_type: TagId, len: usize) -> Result<()> {
+    if elem_type == TagId::End && len != 0 {
+        return Err(NbtErrorKind::InvalidNbtEndTag.into());
+    }
+    check_length(len)
+}
+
+fn check_length(len: usize) -> Result<()> {
+    i32::try_from(len).map(|_| ()).map_err(|_| NbtErrorKind::LengthOutOfRange(len).into())
+}
@@ -1,13 +1,26 @@
-use std::collections::HashMap;
-use std::io::{Error, Read, Seek, SeekFrom};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::fmt::{Display, Formatter};
-use crate::positions::ChunkPos;
-use crate::chunk::Chunk;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use byteorder::{BigEndian, ReadBytesExt};
+use crate::positions::{ChunkPos, RegionPos};
+use crate::chunk::{Chunk, ChunkError, ChunkFilter};
+use crate::util::read_bytes;
 
-#[derive(Debug, Copy, Clone)]
-struct RegionLocation([u8; 4]);
+/// Where a chunk is in the region file, in 4KiB sectors
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RegionLocation([u8; 4]);
 
 impl RegionLocation {
+    /// Only the low 24 bits of `offset` fit
+    pub fn new(offset: u32, sector_count: u8) -> RegionLocation {
+        let offset = offset.to_be_bytes();
+        RegionLocation([offset[1], offset[2], offset[3], sector_count])
+    }
+
     pub fn offset(&self) -> u32 {
         u32::from_be_bytes([0, self.0[0], self.0[1], self.0[2]])
     }
@@ -19,6 +32,10 @@ impl RegionLocation {
     pub fn is_present(&self) -> bool {
         self.0[0] | self.0[1] | self.0[2] | self.0[3] != 0
     }
+
+    pub fn sector_range(&self) -> Range<u32> {
+        self.offset()..self.offset() + self.sector_count() as u32
+    }
 }
 
 impl Display for RegionLocation {
@@ -51,33 +68,313 @@ impl RegionHeader {
         }
         Ok(header)
     }
+
+    pub fn location(&self, pos: ChunkPos) -> RegionLocation {
+        self.locations[Region::get_chunk_index(pos)]
+    }
+
+    /// When the chunk was last saved, in seconds since the unix epoch. `None` if it isn't present.
+    pub fn timestamp(&self, pos: ChunkPos) -> Option<u32> {
+        let index = Region::get_chunk_index(pos);
+        self.locations[index].is_present().then_some(self.timestamps[index])
+    }
+
+    /// The sectors the chunk occupies, `None` if it isn't present
+    pub fn sector_range(&self, pos: ChunkPos) -> Option<Range<u32>> {
+        let location = self.location(pos);
+        location.is_present().then(|| location.sector_range())
+    }
+}
+
+/// What the region knows about a chunk without decoding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub pos: ChunkPos,
+    pub timestamp: u32,
+    pub sectors: Range<u32>,
+    /// The length from the chunk's own header, which counts the compression type byte as well as
+    /// the compressed data
+    pub payload_length: u32,
+    pub compression_type: u8
+}
+
+/// Something wrong with a region file, found by [`Region::verify`]
+#[derive(Debug)]
+pub enum RegionIssue {
+    /// The chunk's sectors include the header's
+    HeaderOverlap(ChunkPos),
+    /// The chunk has an offset but a sector count of 0, so there's nothing to read
+    NoSectors(ChunkPos, u32),
+    /// Two chunks share some of their sectors
+    Overlap(ChunkPos, ChunkPos),
+    /// The chunk's sectors run past the end of the file
+    PastEof(ChunkPos, Range<u32>),
+    /// The length in front of the chunk is more than its sectors can hold
+    LengthTooLarge(ChunkPos, u32, u8),
+    UnknownCompression(ChunkPos, u8),
+    /// The chunk's `xPos`/`zPos` belong in a different slot
+    WrongPosition(ChunkPos, ChunkPos),
+    DecodeFailed(ChunkError)
+}
+
+impl Display for RegionIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegionIssue::HeaderOverlap(pos) => write!(f, "Chunk ({}, {}) overlaps the region header", pos.x, pos.z),
+            RegionIssue::NoSectors(pos, offset) => write!(f, "Chunk ({}, {}) is at sector {} but has no sectors", pos.x, pos.z, offset),
+            RegionIssue::Overlap(first, second) => write!(f, "Chunks ({}, {}) and ({}, {}) share sectors", first.x, first.z, second.x, second.z),
+            RegionIssue::PastEof(pos, sectors) => write!(f, "Chunk ({}, {}) sectors {}..{} run past the end of the file", pos.x, pos.z, sectors.start, sectors.end),
+            RegionIssue::LengthTooLarge(pos, length, sector_count) => write!(f, "Chunk ({}, {}) length {} is larger than its {} sectors", pos.x, pos.z, length, sector_count),
+            RegionIssue::UnknownCompression(pos, compression_type) => write!(f, "Chunk ({}, {}) has unknown compression type {}", pos.x, pos.z, compression_type),
+            RegionIssue::WrongPosition(pos, found) => write!(f, "Chunk ({}, {}) is stored as ({}, {})", pos.x, pos.z, found.x, found.z),
+            RegionIssue::DecodeFailed(err) => write!(f, "{}", err)
+        }
+    }
 }
 
-pub struct Region {
+/// Every chunk position a region hands out, in its chunks, errors and issues, is absolute. That
+/// needs the region's own position, see [`Region::set_pos`], without it the region is taken to be
+/// r.0.0.
+pub struct Region<R = BufReader<File>> {
     pub header: RegionHeader,
-    chunks: HashMap<usize, Chunk>
+    reader: R,
+    pos: Option<RegionPos>,
+    filter: ChunkFilter,
+    /// Recently decoded chunks by index, least recently used first
+    cache: VecDeque<(usize, Chunk)>,
+    cache_size: usize
 }
 
-impl Region {
-    pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<Region, Error> {
-        let header = RegionHeader::parse(reader)?;
-        let mut chunks: HashMap<usize, Chunk> = HashMap::new();
-
-        for x in 0..32 {
-            for z in 0..32 {
-                let index = Region::get_chunk_index(ChunkPos::new(x, z));
-                let location = header.locations[index];
-                if location.is_present() {
-                    reader.seek(SeekFrom::Start((location.offset() * 4096) as u64))?;
-                    let chunk = Chunk::parse(reader)?;
-                    chunks.insert(index, chunk);
+impl<R: Read + Seek> Region<R> {
+    /// Reads just the header, chunks are read and decoded as they're asked for
+    pub fn open(reader: R) -> Result<Region<R>, Error> {
+        Self::open_filtered(reader, ChunkFilter::ALL)
+    }
+
+    pub fn open_filtered(mut reader: R, filter: ChunkFilter) -> Result<Region<R>, Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let header = RegionHeader::parse(&mut reader)?;
+        Ok(Region {
+            header,
+            reader,
+            pos: None,
+            filter,
+            cache: VecDeque::new(),
+            cache_size: 16
+        })
+    }
+
+    pub fn pos(&self) -> Option<RegionPos> {
+        self.pos
+    }
+
+    /// Where the region is, [`World`](crate::world::World) sets this for the regions it opens
+    pub fn set_pos(&mut self, pos: RegionPos) {
+        self.pos = Some(pos);
+    }
+
+    /// The absolute position of the chunk at `index`
+    fn chunk_pos(&self, index: usize) -> ChunkPos {
+        let offset = Region::get_chunk_offset(index);
+        self.pos.map_or(offset, |pos| offset.offset(pos.into()))
+    }
+
+    /// How many decoded chunks [`Region::get_chunk`] keeps around, at least 1
+    pub fn set_cache_size(&mut self, cache_size: usize) {
+        self.cache_size = cache_size.max(1);
+        // Least recently used are at the front
+        while self.cache.len() > self.cache_size {
+            self.cache.pop_front();
+        }
+    }
+
+    /// The chunk exactly as stored: its length, compression type and (compressed) NBT. `None` if
+    /// the chunk hasn't been generated.
+    pub fn read_raw(&mut self, pos: ChunkPos) -> Result<Option<Vec<u8>>, Error> {
+        let location = self.header.location(pos);
+        if !location.is_present() {
+            return Ok(None);
+        }
+        self.reader.seek(SeekFrom::Start(location.offset() as u64 * 4096))?;
+        let length = self.reader.read_u32::<BigEndian>()?;
+        let available = (location.sector_count() as u64 * 4096).saturating_sub(4);
+        if length as u64 > available {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Chunk length {} is larger than its {} sectors", length, location.sector_count())));
+        }
+
+        let mut raw = length.to_be_bytes().to_vec();
+        raw.extend(read_bytes(&mut self.reader, length as usize)?);
+        Ok(Some(raw))
+    }
+
+    pub fn timestamp(&self, pos: ChunkPos) -> Option<u32> {
+        self.header.timestamp(pos)
+    }
+
+    pub fn sector_range(&self, pos: ChunkPos) -> Option<Range<u32>> {
+        self.header.sector_range(pos)
+    }
+
+    /// The length stored in front of the chunk, `None` if it isn't present
+    pub fn payload_length(&mut self, pos: ChunkPos) -> Result<Option<u32>, Error> {
+        Ok(self.read_payload_header(pos)?.map(|(length, _)| length))
+    }
+
+    /// The header, sectors and payload header of every chunk present, in index order. Only the
+    /// first 5 bytes of each chunk are read.
+    pub fn chunk_info_iter(&mut self) -> impl Iterator<Item=Result<ChunkInfo, Error>> + '_ {
+        (0..1024).filter_map(move |index| {
+            let pos = self.chunk_pos(index);
+            let location = self.header.locations[index];
+            if !location.is_present() {
+                return None;
+            }
+            let info = self.read_payload_header(pos).map(|header| {
+                let (payload_length, compression_type) = header.unwrap_or_default();
+                ChunkInfo {
+                    pos,
+                    timestamp: self.header.timestamps[index],
+                    sectors: location.sector_range(),
+                    payload_length,
+                    compression_type
+                }
+            });
+            Some(info)
+        })
+    }
+
+    /// The length and compression type at the start of the chunk
+    fn read_payload_header(&mut self, pos: ChunkPos) -> Result<Option<(u32, u8)>, Error> {
+        let location = self.header.location(pos);
+        if !location.is_present() {
+            return Ok(None);
+        }
+        self.reader.seek(SeekFrom::Start(location.offset() as u64 * 4096))?;
+        let length = self.reader.read_u32::<BigEndian>()?;
+        let compression_type = self.reader.read_u8()?;
+        Ok(Some((length, compression_type)))
+    }
+
+    /// Reads and decodes a chunk, bypassing the cache
+    pub fn read_chunk(&mut self, pos: ChunkPos) -> Result<Option<Chunk>, ChunkError> {
+        let pos = self.chunk_pos(Region::get_chunk_index(pos));
+        let raw = self.read_raw(pos).map_err(|err| ChunkError::from(err).with_pos(pos))?;
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+        Chunk::parse_filtered(&mut &raw[..], self.filter)
+            .map(Some)
+            .map_err(|err| err.with_pos(pos))
+    }
+
+    pub fn get_chunk(&mut self, pos: ChunkPos) -> Result<Option<&Chunk>, ChunkError> {
+        let index = Region::get_chunk_index(pos);
+        if let Some(cached) = self.cache.iter().position(|(cached, _)| *cached == index) {
+            let entry = self.cache.remove(cached).unwrap();
+            self.cache.push_back(entry);
+        } else {
+            let Some(chunk) = self.read_chunk(pos)? else {
+                return Ok(None);
+            };
+            if self.cache.len() >= self.cache_size {
+                self.cache.pop_front();
+            }
+            self.cache.push_back((index, chunk));
+        }
+        Ok(self.cache.back().map(|(_, chunk)| chunk))
+    }
+
+    /// Decodes every chunk in the region in turn, without caching them. A chunk that fails to
+    /// decode doesn't stop the ones after it.
+    pub fn chunk_iter(&mut self) -> impl Iterator<Item=(ChunkPos, Result<Chunk, ChunkError>)> + '_ {
+        (0..1024).filter_map(move |index| {
+            let pos = self.chunk_pos(index);
+            self.read_chunk(pos).transpose().map(|chunk| (pos, chunk))
+        })
+    }
+
+    /// Checks the whole region without stopping at the first problem. Chunks whose sectors and
+    /// payload header look fine are decoded with the region's filter, and their `xPos`/`zPos`
+    /// checked against the slot they're in. Without [`Region::set_pos`] only the position within
+    /// the region is checked.
+    pub fn verify(&mut self) -> Result<Vec<RegionIssue>, Error> {
+        let file_sectors = self.reader.seek(SeekFrom::End(0))?.div_ceil(4096) as u32;
+        let mut issues = Vec::new();
+
+        // Entries without any sectors can't overlap anything, they're reported on their own below
+        let mut ranges: Vec<(Range<u32>, ChunkPos)> = (0..1024)
+            .filter(|&index| self.header.locations[index].sector_count() != 0)
+            .map(|index| (self.header.locations[index].sector_range(), self.chunk_pos(index)))
+            .collect();
+        ranges.sort_by_key(|(range, _)| (range.start, range.end));
+        // The chunk reaching furthest so far, anything starting before its end overlaps it
+        let mut furthest: Option<(u32, ChunkPos)> = None;
+        for (range, pos) in &ranges {
+            if range.start < 2 {
+                issues.push(RegionIssue::HeaderOverlap(*pos));
+            }
+            match furthest {
+                Some((end, other)) if range.start < end => {
+                    issues.push(RegionIssue::Overlap(other, *pos));
+                    if range.end > end {
+                        furthest = Some((range.end, *pos));
+                    }
                 }
+                _ => furthest = Some((range.end, *pos))
             }
         }
 
-        Ok(Region { header, chunks })
+        for index in 0..1024 {
+            let pos = self.chunk_pos(index);
+            let location = self.header.locations[index];
+            if !location.is_present() {
+                continue;
+            }
+            if location.sector_count() == 0 {
+                issues.push(RegionIssue::NoSectors(pos, location.offset()));
+                continue;
+            }
+            if location.sector_range().end > file_sectors {
+                issues.push(RegionIssue::PastEof(pos, location.sector_range()));
+                continue;
+            }
+
+            let (length, compression_type) = match self.read_payload_header(pos) {
+                Ok(header) => header.unwrap_or_default(),
+                Err(err) => {
+                    issues.push(RegionIssue::DecodeFailed(ChunkError::from(err).with_pos(pos)));
+                    continue;
+                }
+            };
+            if length as u64 + 4 > location.sector_count() as u64 * 4096 {
+                issues.push(RegionIssue::LengthTooLarge(pos, length, location.sector_count()));
+                continue;
+            }
+            if !(1..=3).contains(&compression_type) {
+                issues.push(RegionIssue::UnknownCompression(pos, compression_type));
+                continue;
+            }
+
+            match self.read_chunk(pos) {
+                Ok(Some(chunk)) => {
+                    let found = chunk.pos();
+                    let wrong_pos = match self.pos {
+                        Some(_) => found != pos,
+                        None => Region::get_chunk_index(found) != index
+                    };
+                    if wrong_pos {
+                        issues.push(RegionIssue::WrongPosition(pos, found));
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => issues.push(RegionIssue::DecodeFailed(err))
+            }
+        }
+        Ok(issues)
     }
+}
 
+impl Region {
     pub fn get_chunk_index(chunk: ChunkPos) -> usize {
         ((chunk.x & 31) as usize) + (((chunk.z & 31) as usize) * 32)
     }
@@ -88,15 +385,203 @@ impl Region {
             z: ((index / 32) & 31) as i32
         }
     }
-    
-    pub fn get_chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+}
+
+/// Edits a region file: chunks can be written, replaced and deleted, and the file only changes on
+/// [`RegionWriter::flush`], which writes the whole region to a temporary file and renames it over
+/// the original so a crash never leaves a half written region behind.
+pub struct RegionWriter {
+    path: PathBuf,
+    pos: Option<RegionPos>,
+    header: RegionHeader,
+    /// The whole file, header included
+    data: Vec<u8>,
+    /// Which sectors belong to a chunk (or the header)
+    used: Vec<bool>,
+    /// How long the file on disk is, which needn't be whole sectors
+    file_len: u64
+}
+
+impl RegionWriter {
+    /// Loads the region at `path` into memory, or starts an empty one if there's no file yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<RegionWriter, Error> {
+        let path = path.as_ref().to_path_buf();
+        let mut data = if path.exists() { std::fs::read(&path)? } else { Vec::new() };
+        let file_len = data.len() as u64;
+        if data.len() < 8192 {
+            data.resize(8192, 0);
+        }
+        // A file that ends part way through a sector still has a chunk in it, pad rather than cut it off
+        data.resize(data.len().div_ceil(4096) * 4096, 0);
+        let header = RegionHeader::parse(&mut &data[..])?;
+
+        let mut used = vec![false; data.len() / 4096];
+        used[0] = true;
+        used[1] = true;
+        for location in header.locations.iter().filter(|location| location.is_present()) {
+            for sector in location.sector_range() {
+                let sector = sector as usize;
+                if sector >= used.len() {
+                    used.resize(sector + 1, false);
+                }
+                used[sector] = true;
+            }
+        }
+
+        Ok(RegionWriter { path, pos: None, header, data, used, file_len })
+    }
+
+    pub fn header(&self) -> &RegionHeader {
+        &self.header
+    }
+
+    /// Where the region is, only used for the positions in errors. See [`Region::set_pos`].
+    pub fn set_pos(&mut self, pos: RegionPos) {
+        self.pos = Some(pos);
+    }
+
+    /// Serializes `chunk` with the given compression type and stores it at `pos`
+    pub fn write_chunk(&mut self, pos: ChunkPos, chunk: &Chunk, compression_type: u8) -> Result<(), ChunkError> {
+        let pos = self.chunk_pos(Region::get_chunk_index(pos));
+        let mut payload = Vec::new();
+        chunk.write(&mut payload, compression_type).map_err(|err| err.with_pos(pos))?;
+        self.write_raw(pos, &payload).map_err(|err| ChunkError::from(err).with_pos(pos))
+    }
+
+    /// Stores a chunk in the format [`Region::read_raw`] returns it in, replacing whatever was at
+    /// `pos` and setting its timestamp to now
+    pub fn write_raw(&mut self, pos: ChunkPos, payload: &[u8]) -> Result<(), Error> {
+        if payload.len() < 5 || u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize != payload.len() - 4 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Chunk payload length doesn't match its header"));
+        }
+        let sector_count = payload.len().div_ceil(4096);
+        if sector_count > 255 {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Chunk needs {} sectors, at most 255 fit in a region", sector_count)));
+        }
+
         let index = Region::get_chunk_index(pos);
-        self.chunks.get(&index)
+        self.free(self.header.locations[index]);
+        let offset = self.allocate(sector_count);
+        let start = offset * 4096;
+        let end = start + sector_count * 4096;
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[start..start + payload.len()].copy_from_slice(payload);
+        self.data[start + payload.len()..end].fill(0);
+
+        self.header.locations[index] = RegionLocation::new(offset as u32, sector_count as u8);
+        self.header.timestamps[index] = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() as u32);
+        Ok(())
     }
-    
-    pub fn chunk_iter(&self) -> impl Iterator<Item=(ChunkPos, &Chunk)> {
-        self.chunks.iter().map(|(index, chunk)| {
-            (Self::get_chunk_offset(*index), chunk)
-        })
+
+    /// Removes the chunk at `pos`, its sectors are reused by later writes
+    pub fn delete_chunk(&mut self, pos: ChunkPos) {
+        let index = Region::get_chunk_index(pos);
+        self.free(self.header.locations[index]);
+        self.header.locations[index] = RegionLocation([0; 4]);
+        self.header.timestamps[index] = 0;
+    }
+
+    pub fn set_timestamp(&mut self, pos: ChunkPos, timestamp: u32) {
+        self.header.timestamps[Region::get_chunk_index(pos)] = timestamp;
+    }
+
+    /// Moves every chunk to the front of the file in the order they're already in, leaving no gaps
+    /// and trimming each to the sectors its payload needs. Returns how many bytes smaller the
+    /// region will be once flushed than the file on disk is now, and the chunks that were removed
+    /// because they were entirely past the end of the file.
+    pub fn compact(&mut self) -> (u64, Vec<ChunkPos>) {
+        let mut dropped = Vec::new();
+        let mut indices: Vec<usize> = (0..1024).filter(|&index| self.header.locations[index].is_present()).collect();
+        indices.sort_by_key(|&index| self.header.locations[index].offset());
+
+        let mut data = self.data[..8192].to_vec();
+        for index in indices {
+            let location = self.header.locations[index];
+            let start = (location.offset() as usize * 4096).min(self.data.len());
+            let end = (start + location.sector_count() as usize * 4096).min(self.data.len());
+            let mut sectors = &self.data[start..end];
+            if let Some(length) = sectors.get(..4) {
+                let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize + 4;
+                // A length that doesn't fit is left for the game to make sense of
+                if length <= sectors.len() {
+                    sectors = &sectors[..length];
+                }
+            }
+
+            let offset = data.len() / 4096;
+            data.extend_from_slice(sectors);
+            data.resize(data.len().div_ceil(4096) * 4096, 0);
+            let sector_count = data.len() / 4096 - offset;
+            // Chunks that are entirely past the end of the file have nothing left to keep
+            if sector_count == 0 {
+                self.header.locations[index] = RegionLocation([0; 4]);
+                self.header.timestamps[index] = 0;
+                dropped.push(self.chunk_pos(index));
+            } else {
+                self.header.locations[index] = RegionLocation::new(offset as u32, sector_count as u8);
+            }
+        }
+
+        self.used = vec![true; data.len() / 4096];
+        self.data = data;
+        (self.file_len.saturating_sub(self.data.len() as u64), dropped)
+    }
+
+    /// Writes the region out, replacing the original file
+    pub fn flush(&mut self) -> Result<(), Error> {
+        for (index, location) in self.header.locations.iter().enumerate() {
+            self.data[index * 4..index * 4 + 4].copy_from_slice(&location.0);
+        }
+        for (index, timestamp) in self.header.timestamps.iter().enumerate() {
+            self.data[4096 + index * 4..4096 + index * 4 + 4].copy_from_slice(&timestamp.to_be_bytes());
+        }
+
+        // Hidden, and without the .mca extension, so nothing listing the regions picks it up
+        let file_name = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let temp_path = self.path.with_file_name(format!(".{}.tmp", file_name));
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(&self.data)?;
+        temp_file.sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file_len = self.data.len() as u64;
+        Ok(())
+    }
+
+    /// The absolute position of the chunk at `index`, see [`RegionWriter::set_pos`]
+    fn chunk_pos(&self, index: usize) -> ChunkPos {
+        let offset = Region::get_chunk_offset(index);
+        self.pos.map_or(offset, |pos| offset.offset(pos.into()))
+    }
+
+    fn free(&mut self, location: RegionLocation) {
+        if !location.is_present() {
+            return;
+        }
+        for sector in location.sector_range() {
+            // Never hand out the header, even if a broken location points into it
+            if let Some(used) = self.used.get_mut(sector as usize).filter(|_| sector >= 2) {
+                *used = false;
+            }
+        }
+    }
+
+    /// Finds `sector_count` free sectors in a row, the first gap that fits or else the end of the file
+    fn allocate(&mut self, sector_count: usize) -> usize {
+        let mut run_start = 2;
+        for sector in 2..self.used.len() {
+            if self.used[sector] {
+                run_start = sector + 1;
+            } else if sector + 1 - run_start == sector_count {
+                break;
+            }
+        }
+        let end = run_start + sector_count;
+        if self.used.len() < end {
+            self.used.resize(end, false);
+        }
+        self.used[run_start..end].fill(true);
+        run_start
     }
 }
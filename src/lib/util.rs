@@ -1,6 +1,7 @@
-use std::io::{Error, Read};
+use std::io::{Error, ErrorKind, Read, Write};
 use std::mem::ManuallyDrop;
 use std::ptr;
+use byteorder::ByteOrder;
 
 // Utility methods stolen from quartz_nbt
 #[inline]
@@ -19,7 +20,32 @@ pub fn cast_byte_buf_to_signed(buf: Vec<u8>) -> Vec<i8> {
 }
 
 #[inline]
-pub fn read_i32_array<R: Read>(reader: &mut R, len: usize) -> Result<Vec<i32>, Error> {
+pub fn cast_byte_slice_to_unsigned(buf: &[i8]) -> &[u8] {
+    // Safety: i8 and u8 have the same size and alignment, and the lifetime is carried over
+    unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, buf.len()) }
+}
+
+/// Arrays bigger than this aren't allocated up front, since the length might be garbage
+const MAX_PREALLOC: usize = 1 << 20;
+
+/// Reads `len` bytes. Unlike `vec![0; len]` followed by `read_exact`, a bogus length in a truncated
+/// stream only costs as much memory as there is data.
+pub fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOC));
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+#[inline]
+pub fn read_i32_array<B: ByteOrder, R: Read>(reader: &mut R, len: usize) -> Result<Vec<i32>, Error> {
+    if len * 4 > MAX_PREALLOC {
+        let bytes = read_bytes(reader, len * 4)?;
+        return Ok(bytes.chunks_exact(4).map(B::read_i32).collect());
+    }
+
     let mut bytes = ManuallyDrop::new(vec![0i32; len]);
 
     let ptr = bytes.as_mut_ptr() as *mut u8;
@@ -31,11 +57,16 @@ pub fn read_i32_array<R: Read>(reader: &mut R, len: usize) -> Result<Vec<i32>, E
     reader.read_exact(&mut bytes)?;
 
     // Safety: the length of the vec is a multiple of 4, and the alignment is 4
-    Ok(unsafe { convert_be_int_array_in_place::<i32, 4>(bytes, i32::from_be_bytes) })
+    Ok(unsafe { convert_int_array_in_place::<i32, 4>(bytes, |b| B::read_i32(&b)) })
 }
 
 #[inline]
-pub fn read_i64_array<R: Read>(reader: &mut R, len: usize) -> Result<Vec<i64>, Error> {
+pub fn read_i64_array<B: ByteOrder, R: Read>(reader: &mut R, len: usize) -> Result<Vec<i64>, Error> {
+    if len * 8 > MAX_PREALLOC {
+        let bytes = read_bytes(reader, len * 8)?;
+        return Ok(bytes.chunks_exact(8).map(B::read_i64).collect());
+    }
+
     let mut bytes = ManuallyDrop::new(vec![0i64; len]);
 
     let ptr = bytes.as_mut_ptr() as *mut u8;
@@ -47,11 +78,25 @@ pub fn read_i64_array<R: Read>(reader: &mut R, len: usize) -> Result<Vec<i64>, E
     reader.read_exact(&mut bytes)?;
 
     // Safety: the length of the vec is a multiple of 8, and the alignment is 8
-    Ok(unsafe { convert_be_int_array_in_place::<i64, 8>(bytes, i64::from_be_bytes) })
+    Ok(unsafe { convert_int_array_in_place::<i64, 8>(bytes, |b| B::read_i64(&b)) })
+}
+
+#[inline]
+pub fn write_i32_array<B: ByteOrder, W: Write>(writer: &mut W, vals: &[i32]) -> Result<(), Error> {
+    let mut bytes = vec![0; vals.len() * 4];
+    B::write_i32_into(vals, &mut bytes);
+    writer.write_all(&bytes)
+}
+
+#[inline]
+pub fn write_i64_array<B: ByteOrder, W: Write>(writer: &mut W, vals: &[i64]) -> Result<(), Error> {
+    let mut bytes = vec![0; vals.len() * 8];
+    B::write_i64_into(vals, &mut bytes);
+    writer.write_all(&bytes)
 }
 
 #[inline]
-unsafe fn convert_be_int_array_in_place<I, const SIZE: usize>(
+unsafe fn convert_int_array_in_place<I, const SIZE: usize>(
     mut bytes: Vec<u8>,
     convert: fn([u8; SIZE]) -> I,
 ) -> Vec<I> {
@@ -1,10 +1,10 @@
-use std::collections::HashMap;
-use std::fs::{DirEntry, OpenOptions, read_dir};
-use std::io::{Cursor, Error, Seek, SeekFrom, Write};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{DirEntry, File, OpenOptions, read_dir};
+use std::io::{BufReader, Error, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkError, ChunkFilter, ScheduledTick};
 use crate::positions::{ChunkPos, RegionPos};
-use crate::region::Region;
+use crate::region::{Region, RegionIssue, RegionWriter};
 
 // https://minecraft.fandom.com/wiki/Region_file_format
 
@@ -19,6 +19,9 @@ pub struct World {
     overworld_regions: HashMap<RegionPos, Region>,
     nether_regions: HashMap<RegionPos, Region>,
     end_regions: HashMap<RegionPos, Region>,
+    /// The open regions, least recently used first
+    region_order: VecDeque<(Dimension, RegionPos)>,
+    max_open_regions: usize,
     pub world_path: String
 }
 impl World {
@@ -27,16 +30,37 @@ impl World {
             overworld_regions: HashMap::new(),
             nether_regions: HashMap::new(),
             end_regions: HashMap::new(),
+            region_order: VecDeque::new(),
+            max_open_regions: 64,
             world_path: world_path.to_owned()
         }
     }
 
-    pub fn get_chunk(&mut self, pos: ChunkPos, dim: Dimension) -> Result<Option<&Chunk>, Error> {
+    /// Only the chunk itself is decoded, recently used ones are cached by their region
+    pub fn get_chunk(&mut self, pos: ChunkPos, dim: Dimension) -> Result<Option<&Chunk>, ChunkError> {
         let region_x = pos.x >> 5;
         let region_z = pos.z >> 5;
         let region_pos = RegionPos::new(region_x, region_z);
 
-        Ok(self.get_region(region_pos, dim)?.and_then(|r| r.get_chunk(pos)))
+        match self.get_region(region_pos, dim)? {
+            Some(region) => region.get_chunk(pos),
+            None => Ok(None)
+        }
+    }
+
+    /// All the pending block updates in `chunks`, in the order they'll run: by due time, then by
+    /// priority. Ties keep the order they were saved in. Delays count from when each chunk was
+    /// saved, so the due time is the chunk's `LastUpdate` plus the delay.
+    pub fn scheduled_ticks<I: IntoIterator<Item=ChunkPos>>(&mut self, chunks: I, dim: Dimension) -> Result<Vec<ScheduledTick>, ChunkError> {
+        let mut ticks = Vec::new();
+        for pos in chunks {
+            if let Some(chunk) = self.get_chunk(pos, dim)? {
+                let last_update = chunk.last_update;
+                ticks.extend(chunk.tile_tick_iter().map(|tick| (last_update + tick.delay as i64, tick.clone())));
+            }
+        }
+        ticks.sort_by_key(|(due, tick)| (*due, tick.priority));
+        Ok(ticks.into_iter().map(|(_, tick)| tick).collect())
     }
 
     pub fn delete_chunk(&self, pos: ChunkPos, dim: Dimension) -> Result<(), Error> {
@@ -53,33 +77,59 @@ impl World {
         Ok(())
     }
 
-    pub fn get_region(&mut self, pos: RegionPos, dim: Dimension) -> Result<Option<&Region>, Error> {
-        let regions = match dim {
-            Dimension::Overworld => &mut self.overworld_regions,
-            Dimension::Nether => &mut self.nether_regions,
-            Dimension::End => &mut self.end_regions
-        };
-        if regions.contains_key(&pos) {
-            return Ok(regions.get(&pos));
+    /// How many region files are kept open across all dimensions, at least 1. Past that the least
+    /// recently used region is closed, along with the chunks it had cached.
+    pub fn set_max_open_regions(&mut self, max_open_regions: usize) {
+        self.max_open_regions = max_open_regions.max(1);
+        while self.region_order.len() > self.max_open_regions {
+            self.close_oldest_region();
+        }
+    }
+
+    /// Opens the region file and reads its header, keeping it open for later lookups
+    pub fn get_region(&mut self, pos: RegionPos, dim: Dimension) -> Result<Option<&mut Region>, Error> {
+        if let Some(index) = self.region_order.iter().position(|&open| open == (dim, pos)) {
+            let entry = self.region_order.remove(index).unwrap();
+            self.region_order.push_back(entry);
+            return Ok(self.regions_mut(dim).get_mut(&pos));
         }
 
         let path = World::get_region_path(&self.world_path, pos, dim);
         if path.exists() {
-            let region_data = std::fs::read(&path)?;
-            let region = Region::parse(&mut Cursor::new(region_data))?;
+            let mut region = Region::open(BufReader::new(File::open(&path)?))?;
+            region.set_pos(pos);
 
+            if self.region_order.len() >= self.max_open_regions {
+                self.close_oldest_region();
+            }
+            self.region_order.push_back((dim, pos));
+            let regions = self.regions_mut(dim);
             regions.insert(pos, region);
-            Ok(Some(&regions[&pos]))
+            Ok(regions.get_mut(&pos))
         } else {
             Ok(None)
         }
     }
 
-    pub fn get_region_uncached(&self, pos: RegionPos, dim: Dimension) -> Result<Option<Region>, Error> {
+    fn regions_mut(&mut self, dim: Dimension) -> &mut HashMap<RegionPos, Region> {
+        match dim {
+            Dimension::Overworld => &mut self.overworld_regions,
+            Dimension::Nether => &mut self.nether_regions,
+            Dimension::End => &mut self.end_regions
+        }
+    }
+
+    fn close_oldest_region(&mut self) {
+        if let Some((dim, pos)) = self.region_order.pop_front() {
+            self.regions_mut(dim).remove(&pos);
+        }
+    }
+
+    pub fn get_region_uncached(&self, pos: RegionPos, dim: Dimension, filter: ChunkFilter) -> Result<Option<Region>, Error> {
         let path = World::get_region_path(&self.world_path, pos, dim);
         if path.exists() {
-            let region_data = std::fs::read(&path)?;
-            let region = Region::parse(&mut Cursor::new(region_data))?;
+            let mut region = Region::open_filtered(BufReader::new(File::open(&path)?), filter)?;
+            region.set_pos(pos);
 
             Ok(Some(region))
         } else {
@@ -87,6 +137,50 @@ impl World {
         }
     }
 
+    /// A writer for the region file at `pos`, which is created on flush if it doesn't exist yet.
+    /// Regions this world already has open keep reading the old file.
+    pub fn region_writer(&self, pos: RegionPos, dim: Dimension) -> Result<RegionWriter, Error> {
+        let mut writer = RegionWriter::open(World::get_region_path(&self.world_path, pos, dim))?;
+        writer.set_pos(pos);
+        Ok(writer)
+    }
+
+    /// Every problem [`Region::verify`] finds in the region, `None` if the file doesn't exist
+    pub fn verify_region(&self, pos: RegionPos, dim: Dimension) -> Result<Option<Vec<RegionIssue>>, Error> {
+        match self.get_region_uncached(pos, dim, ChunkFilter::ALL)? {
+            Some(mut region) => Ok(Some(region.verify()?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Rewrites the region with its chunks packed together, see [`RegionWriter::compact`]. Returns
+    /// the number of bytes reclaimed and the chunks dropped, the file is left alone if nothing
+    /// changed.
+    pub fn compact_region(&self, pos: RegionPos, dim: Dimension) -> Result<(u64, Vec<ChunkPos>), Error> {
+        if !World::get_region_path(&self.world_path, pos, dim).exists() {
+            return Ok((0, Vec::new()));
+        }
+        let mut writer = self.region_writer(pos, dim)?;
+        let (reclaimed, dropped) = writer.compact();
+        if reclaimed > 0 || !dropped.is_empty() {
+            writer.flush()?;
+        }
+        Ok((reclaimed, dropped))
+    }
+
+    /// Compacts every region in the dimension, returning the total number of bytes reclaimed and
+    /// every chunk dropped
+    pub fn compact_regions(&self, dim: Dimension) -> Result<(u64, Vec<ChunkPos>), Error> {
+        let mut reclaimed = 0;
+        let mut dropped = Vec::new();
+        for pos in self.region_pos_iter(dim)? {
+            let (region_reclaimed, region_dropped) = self.compact_region(pos, dim)?;
+            reclaimed += region_reclaimed;
+            dropped.extend(region_dropped);
+        }
+        Ok((reclaimed, dropped))
+    }
+
     pub fn region_pos_iter(&self, dim: Dimension) -> Result<impl Iterator<Item=RegionPos> + '_, Error> {
         Ok(Self::region_file_iter(&self.world_path, dim)?.map(move |res| {
             let entry = res.unwrap();
@@ -108,7 +202,7 @@ impl World {
                 return false;
             }
             let entry = res.as_ref().unwrap();
-            entry.file_name().into_string().map_or(false, |name| name.contains("r.") && name.contains(".mca"))
+            entry.file_name().into_string().map_or(false, |name| name.starts_with("r.") && name.ends_with(".mca"))
         }))
     }
 
@@ -0,0 +1,206 @@
+mod common;
+
+use mc_utils::chunk::Chunk;
+use mc_utils::nbt::NbtTag;
+use mc_utils::positions::{BlockPos, ChunkPos};
+
+#[test]
+fn tile_entity_at() {
+    // Tile entity positions are absolute, this chunk is (3, -2)
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:3,zPos:-2,TileEntities:[\
+        {id:\"minecraft:chest\",x:48,y:64,z:-30,Items:[]},\
+        {id:\"minecraft:furnace\",x:49,y:64,z:-30,BurnTime:5s},\
+        {id:\"minecraft:sign\",x:48,y:65,z:-30}]}}");
+
+    let furnace = chunk.tile_entity_at(BlockPos::new(49, 64, -30)).unwrap();
+    assert_eq!(furnace.id, "minecraft:furnace");
+    assert_eq!(furnace.pos, BlockPos::new(49, 64, -30));
+    // The id and position are pulled out of the rest of the NBT
+    assert_eq!(furnace.nbt.get("BurnTime"), Some(&NbtTag::Short(5)));
+    assert_eq!(furnace.nbt.len(), 1);
+
+    assert_eq!(chunk.tile_entity_at(BlockPos::new(48, 64, -30)).unwrap().id, "minecraft:chest");
+    assert_eq!(chunk.tile_entity_at(BlockPos::new(48, 65, -30)).unwrap().id, "minecraft:sign");
+    assert!(chunk.tile_entity_at(BlockPos::new(48, 63, -30)).is_none());
+    // Chunk relative coordinates don't find anything
+    assert!(chunk.tile_entity_at(BlockPos::new(0, 64, 2)).is_none());
+}
+
+#[test]
+fn entity_fields() {
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0,Entities:[\
+        {id:\"minecraft:item\",Pos:[1.5d,64.0d,2.25d],Motion:[0.0d,-0.5d,0.125d],Rotation:[90.0f,-10.0f],\
+            UUIDMost:5L,UUIDLeast:-6L,Age:12s,Health:5s,Item:{id:\"minecraft:stone\",Count:3b,Damage:2s,tag:{a:1b}}},\
+        {id:\"minecraft:falling_block\",Pos:[0.5d,70.0d,0.5d],UUID:[I;1,2,-3,4],Time:3b,Block:\"minecraft:sand\",Data:1b,\
+            Passengers:[{id:\"minecraft:tnt\",Pos:[0.5d,71.0d,0.5d],Fuse:80s,Passengers:[{id:\"minecraft:pig\"}]}]},\
+        {id:\"minecraft:xp_orb\",UUIDMost:1L,Value:3s}\
+    ]}}");
+    let entities: Vec<_> = chunk.entity_iter().collect();
+    assert_eq!(entities.len(), 3);
+
+    let item = entities[0];
+    assert_eq!(item.id, "minecraft:item");
+    assert_eq!(item.pos, (1.5, 64.0, 2.25));
+    assert_eq!(item.motion, (0.0, -0.5, 0.125));
+    assert_eq!(item.rotation, (90.0, -10.0));
+    assert_eq!(item.uuid, Some(5 << 64 | (-6i64 as u64 as u128)));
+    assert_eq!(item.age, Some(12));
+    assert_eq!(item.nbt.get("Health"), Some(&NbtTag::Short(5)));
+    let stack = item.item.as_ref().unwrap();
+    assert_eq!((stack.id.as_str(), stack.count, stack.damage), ("minecraft:stone", 3, 2));
+    assert_eq!(stack.tag.as_ref().unwrap().get("a"), Some(&NbtTag::Byte(1)));
+    assert!(item.passengers.is_empty());
+
+    // The 1.16 int array UUID, most significant int first
+    let falling = entities[1];
+    assert_eq!(falling.uuid, Some(1 << 96 | 2 << 64 | (-3i32 as u32 as u128) << 32 | 4));
+    assert_eq!(falling.time, Some(3));
+    assert_eq!(falling.block.as_deref(), Some("minecraft:sand"));
+    assert_eq!(falling.data, Some(1));
+    assert_eq!(falling.motion, (0.0, 0.0, 0.0));
+    assert!(falling.item.is_none());
+    let [tnt] = &falling.passengers[..] else {
+        panic!("{:?}", falling.passengers);
+    };
+    assert_eq!(tnt.id, "minecraft:tnt");
+    assert_eq!(tnt.pos, (0.5, 71.0, 0.5));
+    assert_eq!(tnt.nbt.get("Fuse"), Some(&NbtTag::Short(80)));
+    assert_eq!(tnt.passengers.len(), 1);
+    assert_eq!(tnt.passengers[0].id, "minecraft:pig");
+
+    // Half a UUID isn't one, it stays in the NBT as it was
+    let orb = entities[2];
+    assert_eq!(orb.uuid, None);
+    assert_eq!(orb.nbt.get("UUIDMost"), Some(&NbtTag::Long(1)));
+    assert_eq!(orb.age, None);
+}
+
+/// `values` as SNBT array entries with `suffix`
+fn array(values: impl Iterator<Item=i32>, suffix: &str) -> String {
+    values.map(|value| format!("{value}{suffix}")).collect::<Vec<_>>().join(",")
+}
+
+#[test]
+fn height_map_and_biomes() {
+    // Columns are indexed z << 4 | x, and the byte biomes are unsigned
+    let heights = array(0..256, "");
+    let biomes = array((0..256).map(|index| index as u8 as i8 as i32), "b");
+    let chunk = common::parse_snbt_chunk(&format!("{{Level:{{xPos:3,zPos:-2,HeightMap:[I;{heights}],Biomes:[B;{biomes}]}}}}"));
+    assert_eq!(chunk.height_at(2, 0), Some(2));
+    assert_eq!(chunk.height_at(0, 2), Some(32));
+    assert_eq!(chunk.height_at(15, 15), Some(255));
+    assert_eq!(chunk.biome_at(4, 8), Some(132));
+    // World coordinates work too, only the position within the chunk matters
+    assert_eq!(chunk.height_at(3 * 16 + 2, -2 * 16 + 1), Some(18));
+    assert_eq!(chunk.biome_at(3 * 16 + 2, -2 * 16 + 1), Some(18));
+    // 255 is a biome that hasn't been generated yet
+    assert_eq!(chunk.biome_at(14, 15), Some(254));
+    assert_eq!(chunk.biome_at(15, 15), None);
+
+    // 1.13+ int biomes
+    let biomes = array((0..256).map(|index| index * 10), "");
+    let chunk = common::parse_snbt_chunk(&format!("{{Level:{{xPos:0,zPos:0,Biomes:[I;{biomes}]}}}}"));
+    assert_eq!(chunk.biome_at(15, 15), Some(2550));
+    assert_eq!(chunk.height_at(15, 15), None);
+
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0}}");
+    assert_eq!(chunk.height_at(0, 0), None);
+    assert_eq!(chunk.biome_at(0, 0), None);
+}
+
+#[test]
+fn light() {
+    // A section at Y 1, with block light 3 and sky light 12 at (0, 16, 0) and block light 5 and sky
+    // light 9 at its odd neighbour (1, 16, 0)
+    let mut block_light = vec![0; 2048];
+    let mut sky_light = vec![0; 2048];
+    block_light[0] = 0x53;
+    sky_light[0] = 0x9c_u8 as i8 as i32;
+    let heights = array((0..256).map(|index| if index == 0 { 80 } else { 20 }), "");
+    let chunk = common::parse_snbt_chunk(&format!("{{Level:{{xPos:0,zPos:0,HeightMap:[I;{heights}],Sections:[\
+        {{Y:1b,Blocks:[B;{}],Data:[B;{}],BlockLight:[B;{}],SkyLight:[B;{}]}}]}}}}",
+        array(std::iter::repeat_n(0, 4096), "b"), array(std::iter::repeat_n(0, 2048), "b"),
+        array(block_light.into_iter(), "b"), array(sky_light.into_iter(), "b")));
+    assert_eq!(chunk.block_light_at(BlockPos::new(0, 16, 0)), 3);
+    assert_eq!(chunk.block_light_at(BlockPos::new(1, 16, 0)), 5);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(0, 16, 0)), 12);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(1, 16, 0)), 9);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(2, 16, 0)), 0);
+
+    // Without a section there's no block light, and sky light comes from the height map: full at
+    // and above it, none below
+    assert_eq!(chunk.block_light_at(BlockPos::new(0, 100, 0)), 0);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(0, 80, 0)), 15);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(0, 79, 0)), 0);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(1, 40, 0)), 15);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(1, 5, 0)), 0);
+    assert_eq!(chunk.sky_light_at(BlockPos::new(1, 300, 0)), 15);
+
+    // And with no height map either it's dark
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0}}");
+    assert_eq!(chunk.sky_light_at(BlockPos::new(0, 200, 0)), 0);
+}
+
+#[test]
+fn metadata() {
+    let payload = common::snbt_payload("{DataVersion:1343,Level:{xPos:3,zPos:-2,LastUpdate:1234L,InhabitedTime:99L,\
+        TerrainPopulated:1b,LightPopulated:0b,V:1b}}");
+    let mut chunk = Chunk::parse(&mut &payload[..]).unwrap();
+    assert_eq!(chunk.length as usize, payload.len() - 4);
+    assert_eq!(chunk.compression_type, 3);
+    assert_eq!(chunk.pos(), ChunkPos::new(3, -2));
+    assert_eq!(chunk.last_update, 1234);
+    assert_eq!(chunk.inhabited_time, 99);
+    assert!(chunk.terrain_populated);
+    assert!(!chunk.light_populated);
+    assert_eq!(chunk.version, Some(1));
+
+    // Changes survive a write, in any compression
+    chunk.last_update = -5;
+    chunk.inhabited_time = 1 << 40;
+    chunk.terrain_populated = false;
+    chunk.light_populated = true;
+    chunk.version = Some(2);
+    for compression_type in [1, 2, 3] {
+        let mut written = Vec::new();
+        chunk.write(&mut written, compression_type).unwrap();
+        let reread = Chunk::parse(&mut &written[..]).unwrap();
+        assert_eq!(reread.length as usize, written.len() - 4);
+        assert_eq!(reread.compression_type, compression_type);
+        assert_eq!(reread.pos(), ChunkPos::new(3, -2));
+        assert_eq!(reread.last_update, -5);
+        assert_eq!(reread.inhabited_time, 1 << 40);
+        assert!(!reread.terrain_populated);
+        assert!(reread.light_populated);
+        assert_eq!(reread.version, Some(2));
+    }
+
+    // Missing tags read as their defaults
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0}}");
+    assert_eq!((chunk.last_update, chunk.inhabited_time), (0, 0));
+    assert!(!chunk.terrain_populated && !chunk.light_populated);
+    assert_eq!(chunk.version, None);
+}
+
+#[test]
+fn tile_ticks() {
+    let chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0,TileTicks:[\
+        {i:\"minecraft:water\",x:1,y:2,z:3,t:5,p:-1},\
+        {i:8,x:4,y:5,z:6,t:-2},\
+        {i:\"minecraft:repeater\",x:7,y:8,z:9,t:0,p:2,extra:1b}]}}");
+    let ticks: Vec<_> = chunk.tile_tick_iter().collect();
+    assert_eq!(ticks.len(), 3);
+
+    assert_eq!(ticks[0].block, "minecraft:water");
+    assert_eq!(ticks[0].pos, BlockPos::new(1, 2, 3));
+    assert_eq!((ticks[0].delay, ticks[0].priority), (5, -1));
+    assert!(ticks[0].nbt.is_empty());
+    // Numeric ids from before 1.8, which had no priorities either
+    assert_eq!(ticks[1].block, "8");
+    assert_eq!(ticks[1].pos, BlockPos::new(4, 5, 6));
+    assert_eq!((ticks[1].delay, ticks[1].priority), (-2, 0));
+    // Tags that aren't modeled are kept
+    assert_eq!((ticks[2].delay, ticks[2].priority), (0, 2));
+    assert_eq!(ticks[2].nbt.get("extra"), Some(&NbtTag::Byte(1)));
+    assert_eq!(ticks[2].nbt.len(), 1);
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use mc_utils::chunk::Chunk;
+use mc_utils::nbt::{NbtCompound, NbtList, NbtTag};
+use mc_utils::positions::BlockPos;
+
+fn palette_entry(i: usize) -> NbtCompound {
+    let mut entry = NbtCompound::new();
+    entry.insert("Name", format!("minecraft:block_{i}").as_str());
+    entry
+}
+
+/// A chunk with one section at Y 1, with `palette_len` entries and the given `BlockStates`
+fn palette_chunk(palette_len: usize, block_states: Vec<i64>) -> Chunk {
+    let mut palette = NbtList::new();
+    for i in 0..palette_len {
+        palette.push(palette_entry(i)).unwrap();
+    }
+    let mut section = NbtCompound::new();
+    section.insert("Y", 1i8);
+    section.insert("Palette", palette);
+    section.insert("BlockStates", NbtTag::LongArray(block_states));
+    let mut sections = NbtList::new();
+    sections.push(section).unwrap();
+
+    let mut level = NbtCompound::new();
+    level.insert("xPos", 0);
+    level.insert("zPos", 0);
+    level.insert("Sections", sections);
+    let mut root = NbtCompound::new();
+    root.insert("DataVersion", 2230);
+    root.insert("Level", level);
+    common::parse_chunk(&root)
+}
+
+/// Section index to position, the index being y << 8 | z << 4 | x
+fn index_pos(index: usize) -> BlockPos {
+    BlockPos::new((index & 15) as i32, 16 + (index >> 8) as i32, ((index >> 4) & 15) as i32)
+}
+
+fn state_name(chunk: &Chunk, index: usize) -> Option<String> {
+    chunk.block_state_at(index_pos(index)).map(|state| state.name.clone())
+}
+
+/// Entries packed end to end, split across longs where needed (up to 1.15)
+fn pack_spanning(values: &[usize], bits: usize) -> Vec<i64> {
+    let mut states = vec![0u64; values.len() * bits / 64];
+    for (index, &value) in values.iter().enumerate() {
+        let bit = index * bits;
+        states[bit / 64] |= (value as u64) << (bit % 64);
+        if bit % 64 + bits > 64 {
+            states[bit / 64 + 1] |= (value as u64) >> (64 - bit % 64);
+        }
+    }
+    states.into_iter().map(|state| state as i64).collect()
+}
+
+/// As many whole entries as fit in each long, the rest is padding (1.16+)
+fn pack_padded(values: &[usize], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+    let mut states = vec![0u64; values.len().div_ceil(per_long)];
+    for (index, &value) in values.iter().enumerate() {
+        states[index / per_long] |= (value as u64) << (index % per_long * bits);
+    }
+    states.into_iter().map(|state| state as i64).collect()
+}
+
+/// Uses every palette entry, in an order that doesn't line up with the longs
+fn sample_values(palette_len: usize) -> Vec<usize> {
+    (0..4096).map(|index| (index * 7 + index / 5) % palette_len).collect()
+}
+
+fn assert_states(chunk: &Chunk, values: &[usize]) {
+    for (index, &value) in values.iter().enumerate() {
+        assert_eq!(state_name(chunk, index), Some(format!("minecraft:block_{value}")), "index {index}");
+    }
+}
+
+#[test]
+fn spanning_layout() {
+    // 4 bits divides 64 so both layouts are the same, 5 and 9 bits have entries crossing longs
+    for (palette_len, bits, longs) in [(16, 4, 256), (17, 5, 320), (300, 9, 576)] {
+        let values = sample_values(palette_len);
+        let states = pack_spanning(&values, bits);
+        assert_eq!(states.len(), longs);
+        assert_states(&palette_chunk(palette_len, states), &values);
+    }
+}
+
+#[test]
+fn padded_layout() {
+    for (palette_len, bits, longs) in [(16, 4, 256), (17, 5, 342), (300, 9, 586)] {
+        let values = sample_values(palette_len);
+        let states = pack_padded(&values, bits);
+        assert_eq!(states.len(), longs);
+        assert_states(&palette_chunk(palette_len, states), &values);
+    }
+}
+
+#[test]
+fn fixed_vectors() {
+    // 5 bits spanning: entry 12 is bits 60..65, the low 4 in the first long and the top 1 in the second
+    let mut states = vec![0; 320];
+    states[0] = 0b0101 << 60 | 0b00011;
+    states[1] = 0b1;
+    let chunk = palette_chunk(32, states);
+    assert_eq!(state_name(&chunk, 0).unwrap(), "minecraft:block_3");
+    assert_eq!(state_name(&chunk, 12).unwrap(), "minecraft:block_21");
+    assert_eq!(state_name(&chunk, 13).unwrap(), "minecraft:block_0");
+
+    // 5 bits padded: 12 entries per long, the top 4 bits unused
+    let mut states = vec![0; 342];
+    states[0] = 0b1111 << 60 | 0b10101 << 55;
+    states[1] = 0b00110;
+    let chunk = palette_chunk(32, states);
+    assert_eq!(state_name(&chunk, 11).unwrap(), "minecraft:block_21");
+    assert_eq!(state_name(&chunk, 12).unwrap(), "minecraft:block_6");
+
+    // 9 bits spanning: entry 7 is bits 63..72
+    let mut states = vec![0; 576];
+    states[0] = i64::MIN;
+    states[1] = 0b10000000;
+    let chunk = palette_chunk(300, states);
+    assert_eq!(state_name(&chunk, 7).unwrap(), "minecraft:block_257");
+
+    // 9 bits padded: 7 entries per long, entry 7 starts the second long
+    let mut states = vec![0; 586];
+    states[0] = i64::MIN;
+    states[1] = 0b100000001;
+    let chunk = palette_chunk(300, states);
+    assert_eq!(state_name(&chunk, 6).unwrap(), "minecraft:block_0");
+    assert_eq!(state_name(&chunk, 7).unwrap(), "minecraft:block_257");
+
+    // 4 bits, the same either way
+    let mut states = vec![0; 256];
+    states[0] = 0xf << 60 | 0x2;
+    let chunk = palette_chunk(16, states);
+    assert_eq!(state_name(&chunk, 0).unwrap(), "minecraft:block_2");
+    assert_eq!(state_name(&chunk, 15).unwrap(), "minecraft:block_15");
+}
+
+#[test]
+fn bit_width_follows_palette_length() {
+    // max(4, ceil(log2(len))): even a single entry palette uses 4 bits
+    for (palette_len, bits) in [(1, 4), (2, 4), (16, 4), (17, 5), (32, 5), (33, 6), (64, 6), (65, 7), (256, 8), (257, 9)] {
+        let values = sample_values(palette_len);
+        let chunk = palette_chunk(palette_len, pack_spanning(&values, bits));
+        assert_states(&chunk, &values);
+        let chunk = palette_chunk(palette_len, pack_padded(&values, bits));
+        assert_states(&chunk, &values);
+    }
+}
+
+#[test]
+fn length_picks_the_layout() {
+    // The same 5 bit values read back correctly only with the layout their length says
+    let values = sample_values(17);
+    let spanning = pack_spanning(&values, 5);
+    let padded = pack_padded(&values, 5);
+    assert_ne!(spanning.len(), padded.len());
+    assert_states(&palette_chunk(17, spanning.clone()), &values);
+    assert_states(&palette_chunk(17, padded.clone()), &values);
+
+    // Spanning data one long short is read as padded, which scrambles it without reading past the end
+    let mut short = spanning;
+    short.pop();
+    let chunk = palette_chunk(17, short);
+    assert_ne!((0..4096).map(|index| state_name(&chunk, index)).collect::<Vec<_>>(),
+               values.iter().map(|value| Some(format!("minecraft:block_{value}"))).collect::<Vec<_>>());
+
+    // A value past the end of the palette has no state
+    let mut states = vec![0; 320];
+    states[0] = 31;
+    let chunk = palette_chunk(17, states);
+    assert_eq!(state_name(&chunk, 0), None);
+    assert_eq!(state_name(&chunk, 1).unwrap(), "minecraft:block_0");
+}
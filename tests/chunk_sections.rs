@@ -0,0 +1,156 @@
+mod common;
+
+use mc_utils::block::Block;
+use mc_utils::chunk::Chunk;
+use mc_utils::nbt::{LeafTag, NbtWriter, TagId};
+
+/// A section with a single block (with the id of the section's Y + 1) at local (1, 2, 3). `y_last`
+/// writes the `Y` tag after the block arrays instead of before.
+fn write_section(writer: &mut NbtWriter<Vec<u8>>, y: i8, y_last: bool) {
+    let mut blocks = vec![0i8; 4096];
+    blocks[1 | 2 << 8 | 3 << 4] = y + 1;
+
+    writer.begin_compound_element().unwrap();
+    if !y_last {
+        writer.write_leaf("Y", &LeafTag::Byte(y)).unwrap();
+    }
+    writer.write_leaf("Blocks", &LeafTag::ByteArray(blocks)).unwrap();
+    writer.write_leaf("Data", &LeafTag::ByteArray(vec![0; 2048])).unwrap();
+    if y_last {
+        writer.write_leaf("Y", &LeafTag::Byte(y)).unwrap();
+    }
+    writer.end_compound().unwrap();
+}
+
+/// A chunk with the given sections
+fn chunk_with_sections(sections: &[(i8, bool)]) -> Chunk {
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.begin_compound("Level").unwrap();
+    writer.write_leaf("xPos", &LeafTag::Int(0)).unwrap();
+    writer.write_leaf("zPos", &LeafTag::Int(0)).unwrap();
+    writer.begin_list("Sections", TagId::Compound, sections.len()).unwrap();
+    for &(y, y_last) in sections {
+        write_section(&mut writer, y, y_last);
+    }
+    writer.end_list().unwrap();
+    writer.end_compound().unwrap();
+    writer.end_compound().unwrap();
+    Chunk::parse(&mut &common::payload(&writer.finish().unwrap())[..]).unwrap()
+}
+
+fn non_air(chunk: &Chunk) -> Vec<(i32, i32, i32, u16)> {
+    chunk.block_iter()
+        .filter(|(_, block)| block.block_id != 0)
+        .map(|(pos, block)| (pos.x, pos.y, pos.z, block.block_id))
+        .collect()
+}
+
+#[test]
+fn contiguous_sections() {
+    let chunk = chunk_with_sections(&[(0, false), (1, false)]);
+    assert_eq!(chunk.block_at((1, 2, 3).into()).block_id, 1);
+    assert_eq!(chunk.block_at((1, 18, 3).into()).block_id, 2);
+    assert_eq!(non_air(&chunk), vec![(1, 2, 3, 1), (1, 18, 3, 2)]);
+}
+
+#[test]
+fn gapped_sections() {
+    let chunk = chunk_with_sections(&[(0, false), (5, false)]);
+    assert_eq!(chunk.block_at((1, 2, 3).into()).block_id, 1);
+    assert_eq!(chunk.block_at((1, 5 * 16 + 2, 3).into()).block_id, 6);
+    // Would have been section 5 if sections were indexed by list order
+    assert_eq!(chunk.block_at((1, 16 + 2, 3).into()).block_id, 0);
+    assert_eq!(non_air(&chunk), vec![(1, 2, 3, 1), (1, 82, 3, 6)]);
+}
+
+#[test]
+fn out_of_order_sections() {
+    let chunk = chunk_with_sections(&[(7, false), (2, true), (4, false)]);
+    assert_eq!(chunk.block_at((1, 7 * 16 + 2, 3).into()).block_id, 8);
+    assert_eq!(chunk.block_at((1, 2 * 16 + 2, 3).into()).block_id, 3);
+    assert_eq!(chunk.block_at((1, 4 * 16 + 2, 3).into()).block_id, 5);
+    assert_eq!(non_air(&chunk), vec![(1, 34, 3, 3), (1, 66, 3, 5), (1, 114, 3, 8)]);
+}
+
+#[test]
+fn out_of_range_heights_are_air() {
+    let chunk = chunk_with_sections(&[(15, false)]);
+    assert_eq!(chunk.block_at((1, 15 * 16 + 2, 3).into()).block_id, 16);
+    assert_eq!(chunk.block_at((1, -14, 3).into()).block_id, 0);
+    assert_eq!(chunk.block_at((1, 256 + 2, 3).into()).block_id, 0);
+    assert_eq!(chunk.block_at((1, i32::MAX, 3).into()).block_id, 0);
+}
+
+/// A chunk with one section at `y` made of the given arrays, `Add` left out if it's empty
+fn chunk_with_arrays(y: i8, blocks: Vec<i8>, add: Vec<i8>, data: Vec<i8>) -> Chunk {
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.begin_compound("Level").unwrap();
+    writer.write_leaf("xPos", &LeafTag::Int(0)).unwrap();
+    writer.write_leaf("zPos", &LeafTag::Int(0)).unwrap();
+    writer.begin_list("Sections", TagId::Compound, 1).unwrap();
+    writer.begin_compound_element().unwrap();
+    writer.write_leaf("Y", &LeafTag::Byte(y)).unwrap();
+    writer.write_leaf("Blocks", &LeafTag::ByteArray(blocks)).unwrap();
+    if !add.is_empty() {
+        writer.write_leaf("Add", &LeafTag::ByteArray(add)).unwrap();
+    }
+    writer.write_leaf("Data", &LeafTag::ByteArray(data)).unwrap();
+    writer.end_compound().unwrap();
+    writer.end_list().unwrap();
+    writer.end_compound().unwrap();
+    writer.end_compound().unwrap();
+    Chunk::parse(&mut &common::payload(&writer.finish().unwrap())[..]).unwrap()
+}
+
+#[test]
+fn add_supplies_the_high_id_bits() {
+    // Index 0 takes the low nibbles of Add and Data, index 1 the high ones, and index 4095 (15, 15,
+    // 15) the high nibbles of the last bytes
+    let mut blocks = vec![0i8; 4096];
+    let mut add = vec![0i8; 2048];
+    let mut data = vec![0i8; 2048];
+    blocks[0] = 0xab_u8 as i8;
+    blocks[1] = 0xcd_u8 as i8;
+    add[0] = 0x21;
+    data[0] = 0x95_u8 as i8;
+    blocks[4095] = 0x01;
+    add[2047] = 0xf0_u8 as i8;
+    data[2047] = 0xf0_u8 as i8;
+    let chunk = chunk_with_arrays(2, blocks, add, data);
+
+    assert_eq!(chunk.block_at((0, 32, 0).into()), Block::new(0x1ab, 5));
+    assert_eq!(chunk.block_at((1, 32, 0).into()), Block::new(0x2cd, 9));
+    assert_eq!(chunk.block_at((15, 47, 15).into()), Block::new(0xf01, 15));
+    assert_eq!(chunk.data_at((0, 32, 0).into()), 5);
+    assert_eq!(chunk.data_at((1, 32, 0).into()), 9);
+    assert_eq!(chunk.data_at((15, 47, 15).into()), 15);
+    // The neighbours sharing those bytes are untouched
+    assert_eq!(chunk.block_at((14, 47, 15).into()), Block::AIR);
+
+    let blocks: Vec<_> = chunk.block_iter()
+        .filter(|(_, block)| *block != Block::AIR)
+        .map(|(pos, block)| (pos.x, pos.y, pos.z, block.block_id, block.data))
+        .collect();
+    assert_eq!(blocks, vec![(0, 32, 0, 0x1ab, 5), (1, 32, 0, 0x2cd, 9), (15, 47, 15, 0xf01, 15)]);
+}
+
+#[test]
+fn data_without_add() {
+    let mut blocks = vec![0i8; 4096];
+    let mut data = vec![0i8; 2048];
+    // Ids above 127 are still unsigned without Add
+    blocks[2 | 3 << 4] = 0xc8_u8 as i8;
+    blocks[3 | 3 << 4] = 1;
+    data[(2 | 3 << 4) >> 1] = 0x7e;
+    let chunk = chunk_with_arrays(0, blocks, Vec::new(), data);
+
+    assert_eq!(chunk.block_at((2, 0, 3).into()), Block::new(200, 0xe));
+    assert_eq!(chunk.block_at((3, 0, 3).into()), Block::new(1, 7));
+    assert_eq!(chunk.data_at((2, 0, 3).into()), 0xe);
+    assert_eq!(chunk.data_at((3, 0, 3).into()), 7);
+    // Data is read even where there's air, and positions outside any section have none
+    assert_eq!(chunk.data_at((2, 1, 3).into()), 0);
+    assert_eq!(chunk.data_at((2, 16, 3).into()), 0);
+}
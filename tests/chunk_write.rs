@@ -0,0 +1,153 @@
+mod common;
+
+use mc_utils::chunk::{Chunk, Entity};
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::{LeafTag, NbtCompound, NbtList, NbtTag, NbtWriter, TagId};
+use mc_utils::positions::BlockPos;
+
+/// The writer puts modeled tags first, so compare with every compound's keys sorted. Lists keep
+/// their element type, so an empty list of the wrong type doesn't compare equal.
+fn sorted(tag: &NbtTag) -> NbtTag {
+    match tag {
+        NbtTag::Compound(compound) => NbtTag::Compound(sorted_compound(compound)),
+        NbtTag::List(list) => {
+            let mut sorted_list = NbtList::with_type(list.elem_type());
+            for element in list.iter() {
+                sorted_list.push(sorted(element)).unwrap();
+            }
+            NbtTag::List(sorted_list)
+        }
+        _ => tag.clone()
+    }
+}
+
+fn sorted_compound(compound: &NbtCompound) -> NbtCompound {
+    let mut entries: Vec<_> = compound.iter().collect();
+    entries.sort_by_key(|&(key, _)| key.to_owned());
+    let mut sorted_compound = NbtCompound::new();
+    for (key, tag) in entries {
+        sorted_compound.insert(key, sorted(tag));
+    }
+    sorted_compound
+}
+
+/// Parses `snbt` as a chunk, writes it back out and checks the tree didn't change
+fn assert_round_trip(snbt: &str) {
+    let nbt = parse_snbt(snbt).unwrap();
+    let chunk = common::parse_chunk(&nbt);
+
+    let mut written = Vec::new();
+    chunk.write(&mut written, 3).unwrap();
+    let reread = NbtCompound::read(&mut &written[5..]).unwrap();
+    assert_eq!(sorted_compound(&reread), sorted_compound(&nbt));
+
+    // And the written chunk parses back to the same thing
+    let mut rewritten = Vec::new();
+    Chunk::parse(&mut &written[..]).unwrap().write(&mut rewritten, 3).unwrap();
+    assert_eq!(rewritten, written);
+}
+
+fn section(y: i8) -> String {
+    let blocks = vec!["1b"; 4096].join(",");
+    let data = vec!["0b"; 2048].join(",");
+    format!("{{Y:{y}b,Blocks:[B;{blocks}],Data:[B;{data}],BlockLight:[B;{data}],SkyLight:[B;{data}]}}")
+}
+
+#[test]
+fn full_chunk() {
+    let heights = vec!["64"; 256].join(",");
+    let biomes = vec!["1b"; 256].join(",");
+    assert_round_trip(&format!("{{DataVersion:1343,Level:{{xPos:3,zPos:-2,LastUpdate:1234L,InhabitedTime:99L,\
+        TerrainPopulated:1b,LightPopulated:0b,V:1b,HeightMap:[I;{heights}],Biomes:[B;{biomes}],\
+        Sections:[{},{}],\
+        Entities:[{{id:\"minecraft:item\",Pos:[1.5d,64.0d,2.5d],Motion:[0.0d,0.0d,0.0d],Rotation:[0.0f,0.0f],\
+            UUIDMost:5L,UUIDLeast:-6L,Age:12s,Health:5s,Item:{{id:\"minecraft:stone\",Count:3b,Damage:0s}}}}],\
+        TileEntities:[{{id:\"minecraft:chest\",x:48,y:64,z:-30,Items:[{{Slot:0b,id:\"minecraft:dirt\",Count:64b,Damage:0s}}]}}],\
+        TileTicks:[{{i:\"minecraft:water\",x:48,y:63,z:-30,t:5,p:0}}],\
+        Structures:{{References:{{}},Starts:{{}}}}}}}}", section(0), section(3)));
+}
+
+#[test]
+fn missing_tags_stay_missing() {
+    // No population flags, update times, height map, biomes or tile ticks
+    assert_round_trip("{Level:{xPos:0,zPos:0,Sections:[],Entities:[],TileEntities:[]}}");
+    assert_round_trip("{Level:{xPos:0,zPos:0}}");
+}
+
+#[test]
+fn entity_tag_types_are_kept() {
+    // The 1.16 int array UUID, a byte Time and an int Age
+    assert_round_trip("{Level:{xPos:0,zPos:0,Entities:[\
+        {id:\"minecraft:falling_block\",Pos:[0.5d,70.0d,0.5d],UUID:[I;1,2,3,4],Time:3b,Age:7,\
+            Passengers:[{id:\"minecraft:tnt\",Pos:[0.5d,71.0d,0.5d],Fuse:80s}]},\
+        {id:\"minecraft:item\",UUIDMost:1L,Age:5,Item:{id:\"minecraft:stone\"}},\
+        {id:\"minecraft:xp_orb\",UUIDMost:1L,UUIDLeast:2L,UUID:[I;5,6,7,8],Value:3s}\
+    ]}}");
+}
+
+#[test]
+fn items_and_ticks_keep_their_tags() {
+    assert_round_trip("{Level:{xPos:0,zPos:0,\
+        TileEntities:[{id:\"minecraft:hopper\",x:1,y:2,z:3,Items:[\
+            {Slot:0b,id:\"minecraft:stone\",Count:1b},\
+            {Slot:1b,id:1s,Damage:3s,tag:{display:{Name:\"x\"}}},\
+            {Slot:2b,id:\"minecraft:dirt\",count:5}]}],\
+        TileTicks:[{i:\"minecraft:water\",x:1,y:2,z:3,t:0,extra:1b},{i:8,x:1,y:2,z:4,t:-1,p:2}]}}");
+}
+
+/// The element type of the list at `Level.<name>` in a written chunk
+fn list_type(written: &[u8], name: &str) -> Option<TagId> {
+    let reread = NbtCompound::read(&mut &written[5..]).unwrap();
+    match reread.get("Level")?.as_compound()?.get(name)? {
+        NbtTag::List(list) => Some(list.elem_type()),
+        _ => None
+    }
+}
+
+#[test]
+fn empty_lists_keep_their_element_type() {
+    // Vanilla saves empty lists as lists of TAG_End, but a list of compounds is kept as one too
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.begin_compound("Level").unwrap();
+    writer.write_leaf("xPos", &LeafTag::Int(0)).unwrap();
+    writer.write_leaf("zPos", &LeafTag::Int(0)).unwrap();
+    for (name, elem_type) in [("Sections", TagId::End), ("Entities", TagId::End), ("TileEntities", TagId::Compound),
+                              ("TileTicks", TagId::End)] {
+        writer.begin_list(name, elem_type, 0).unwrap();
+        writer.end_list().unwrap();
+    }
+    writer.end_compound().unwrap();
+    writer.end_compound().unwrap();
+    let nbt = writer.finish().unwrap();
+    let mut chunk = Chunk::parse(&mut &common::payload(&nbt)[..]).unwrap();
+
+    let mut written = Vec::new();
+    chunk.write(&mut written, 3).unwrap();
+    // Nothing was reordered, so the bytes are the same too
+    assert_eq!(written, common::payload(&nbt));
+    assert_eq!(list_type(&written, "Entities"), Some(TagId::End));
+    assert_eq!(list_type(&written, "TileEntities"), Some(TagId::Compound));
+
+    // A list that gets something added is a list of compounds, and goes back to the type it was read
+    // with once emptied again
+    chunk.add_entity(Entity::new("minecraft:pig"));
+    let mut written = Vec::new();
+    chunk.write(&mut written, 3).unwrap();
+    assert_eq!(list_type(&written, "Entities"), Some(TagId::Compound));
+    chunk.remove_entities(|_| true);
+    let mut written = Vec::new();
+    chunk.write(&mut written, 3).unwrap();
+    assert_eq!(list_type(&written, "Entities"), Some(TagId::End));
+
+    // A list that had compounds when it was read stays a list of compounds, and one the chunk never
+    // had isn't added just because something came and went
+    let mut chunk = common::parse_snbt_chunk("{Level:{xPos:0,zPos:0,TileEntities:[{id:\"minecraft:chest\",x:0,y:0,z:0}]}}");
+    chunk.remove_tile_entity(BlockPos::new(0, 0, 0));
+    chunk.add_entity(Entity::new("minecraft:pig"));
+    chunk.remove_entities(|_| true);
+    let mut written = Vec::new();
+    chunk.write(&mut written, 3).unwrap();
+    assert_eq!(list_type(&written, "TileEntities"), Some(TagId::Compound));
+    assert_eq!(list_type(&written, "Entities"), None);
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the integration tests, not every test uses all of them
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use mc_utils::chunk::Chunk;
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::NbtCompound;
+
+/// `nbt` as an uncompressed chunk payload, the way a region file stores it: the length (which
+/// counts the compression type too), compression type 3, then the NBT
+pub fn payload(nbt: &[u8]) -> Vec<u8> {
+    let mut payload = ((nbt.len() + 1) as u32).to_be_bytes().to_vec();
+    payload.push(3);
+    payload.extend_from_slice(nbt);
+    payload
+}
+
+pub fn compound_payload(nbt: &NbtCompound) -> Vec<u8> {
+    payload(&nbt.write(Vec::new(), "").unwrap())
+}
+
+pub fn snbt_payload(snbt: &str) -> Vec<u8> {
+    compound_payload(&parse_snbt(snbt).unwrap())
+}
+
+pub fn parse_chunk(nbt: &NbtCompound) -> Chunk {
+    Chunk::parse(&mut &compound_payload(nbt)[..]).unwrap()
+}
+
+pub fn parse_snbt_chunk(snbt: &str) -> Chunk {
+    Chunk::parse(&mut &snbt_payload(snbt)[..]).unwrap()
+}
+
+/// A fresh directory for one test, removed again by [`cleanup`]
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mc_utils_{}_{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn cleanup(dir: &Path) {
+    std::fs::remove_dir_all(dir).unwrap();
+}
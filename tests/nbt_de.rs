@@ -0,0 +1,115 @@
+use java_string::{JavaCodePoint, JavaString};
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::{from_reader, NbtList};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+enum GameMode {
+    Survival,
+    Creative,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Item {
+    id: String,
+    #[serde(rename = "Count")]
+    count: i8,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Player {
+    #[serde(rename = "OnGround")]
+    on_ground: bool,
+    #[serde(rename = "Pos")]
+    pos: (f64, f64),
+    #[serde(rename = "UUID")]
+    uuid: [i32; 2],
+    #[serde(rename = "CustomName")]
+    custom_name: Option<String>,
+    #[serde(rename = "SelectedItem")]
+    selected_item: Option<Item>,
+    #[serde(rename = "Mode")]
+    mode: GameMode,
+    #[serde(rename = "Book")]
+    book: JavaString,
+    #[serde(rename = "Inventory")]
+    inventory: Vec<Item>,
+    #[serde(rename = "Last")]
+    last: i32,
+}
+
+fn lone_surrogate() -> JavaString {
+    let mut string = JavaString::from("page ");
+    string.push_java(JavaCodePoint::from_u32(0xDBFF).unwrap());
+    string
+}
+
+fn player_nbt() -> Vec<u8> {
+    let mut compound = parse_snbt(concat!(
+        "{Unknown:{Nested:[{a:1,b:[L;1L,2L]},{c:\"x\"}],Arr:[B;1b,2b]},",
+        "OnGround:1b,",
+        "Pos:[1.5d,64.0d,-3.25d],",
+        "UUID:[I;1,2,3,4],",
+        "SelectedItem:{id:\"minecraft:stone\",Count:3b,tag:{Damage:0}},",
+        "Mode:\"Creative\",",
+        "Inventory:[{Slot:0b,id:\"minecraft:dirt\",Count:64b},{Slot:1b,id:\"minecraft:torch\",Count:1b}],",
+        "Strings:[\"a\",\"b\"],",
+        "Last:42}",
+    )).unwrap();
+    compound.insert("Book", lone_surrogate());
+    compound.insert("Later", NbtList::new());
+    compound.write(Vec::new(), "").unwrap()
+}
+
+#[test]
+fn deserializes_a_struct() {
+    let player: Player = from_reader(&player_nbt()[..]).unwrap();
+    assert_eq!(player, Player {
+        on_ground: true,
+        // The rest of the list is skipped over
+        pos: (1.5, 64.0),
+        uuid: [1, 2],
+        custom_name: None,
+        selected_item: Some(Item { id: "minecraft:stone".into(), count: 3 }),
+        mode: GameMode::Creative,
+        book: lone_surrogate(),
+        inventory: vec![
+            Item { id: "minecraft:dirt".into(), count: 64 },
+            Item { id: "minecraft:torch".into(), count: 1 },
+        ],
+        last: 42,
+    });
+}
+
+#[test]
+fn non_utf8_strings_are_bytes() {
+    #[derive(Deserialize)]
+    struct Book {
+        #[serde(rename = "Book")]
+        _book: String,
+    }
+    assert!(from_reader::<_, Book>(&player_nbt()[..]).is_err());
+}
+
+#[test]
+fn errors_point_at_the_tag() {
+    #[derive(Debug, Deserialize)]
+    struct Mode {
+        #[serde(rename = "Mode")]
+        _mode: GameMode,
+    }
+    let bytes = parse_snbt("{Mode:\"Spectator\"}").unwrap().write(Vec::new(), "").unwrap();
+    let err = from_reader::<_, Mode>(&bytes[..]).unwrap_err();
+    assert_eq!(err.path().to_string(), "Mode");
+
+    #[derive(Debug, Deserialize)]
+    struct Flag {
+        #[serde(rename = "Flag")]
+        _flag: bool,
+    }
+    let bytes = parse_snbt("{Flag:\"yes\"}").unwrap().write(Vec::new(), "").unwrap();
+    assert!(from_reader::<_, Flag>(&bytes[..]).is_err());
+
+    let bytes = parse_snbt("{Other:1}").unwrap().write(Vec::new(), "").unwrap();
+    assert!(from_reader::<_, Flag>(&bytes[..]).is_err());
+}
@@ -0,0 +1,79 @@
+use std::io::Write;
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::{read_file, visit_nbt_auto, NbtCompound, NbtCompression, NbtErrorKind, NbtTreeVisitor};
+
+fn sample() -> NbtCompound {
+    parse_snbt("{Data:{LevelName:\"world\",Time:1234L,GameRules:{doFireTick:\"false\"}},Version:[I;1,2]}").unwrap()
+}
+
+fn uncompressed() -> Vec<u8> {
+    sample().write(Vec::new(), "").unwrap()
+}
+
+fn gzip() -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&uncompressed()).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zlib(level: Compression) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(&uncompressed()).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn visit(bytes: &[u8]) -> mc_utils::nbt::Result<NbtCompound> {
+    let mut visitor = NbtTreeVisitor::new();
+    visit_nbt_auto(bytes, &mut visitor)?;
+    Ok(visitor.into_root())
+}
+
+#[test]
+fn detects_compression() {
+    assert_eq!(NbtCompression::detect(&gzip()), Some(NbtCompression::Gzip));
+    // Every compression level has a different zlib header
+    for level in [Compression::none(), Compression::fast(), Compression::default(), Compression::best()] {
+        assert_eq!(NbtCompression::detect(&zlib(level)), Some(NbtCompression::Zlib), "{level:?}");
+    }
+    assert_eq!(NbtCompression::detect(&uncompressed()), Some(NbtCompression::Uncompressed));
+
+    assert_eq!(NbtCompression::detect(&[]), None);
+    assert_eq!(NbtCompression::detect(&[0x1f]), None);
+    assert_eq!(NbtCompression::detect(b"{Data:1}"), None);
+    // Looks like deflate but the header checksum is off
+    assert_eq!(NbtCompression::detect(&[0x78, 0x9d]), None);
+}
+
+#[test]
+fn reads_every_compression() {
+    for bytes in [gzip(), zlib(Compression::default()), zlib(Compression::none()), uncompressed()] {
+        assert_eq!(visit(&bytes).unwrap(), sample());
+    }
+}
+
+#[test]
+fn reads_files() {
+    let dir = std::env::temp_dir().join(format!("mc_utils_nbt_file_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, bytes) in [("gzip.dat", gzip()), ("zlib.dat", zlib(Compression::best())), ("raw.nbt", uncompressed())] {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        assert_eq!(read_file(&path).unwrap(), sample(), "{name}");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unrecognised_input_errors() {
+    for bytes in [&b"{Data:1}"[..], &[], &[0x0b, 0x00, 0x00], &[0x1f]] {
+        let err = visit(bytes).unwrap_err();
+        assert!(matches!(err.kind(), NbtErrorKind::UnknownCompression(header) if header.len() == bytes.len().min(2)), "{err}");
+    }
+
+    // Recognised, but the data after the header is broken
+    let mut truncated = gzip();
+    truncated.truncate(truncated.len() / 2);
+    assert!(visit(&truncated).is_err());
+}
@@ -0,0 +1,103 @@
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::{from_reader_with, LeafTag, NbtCompound, NbtErrorKind, NbtFlavor, NbtLimits, NbtWriter, TagId};
+use serde::Deserialize;
+
+const FLAVORS: [NbtFlavor; 3] = [NbtFlavor::Java, NbtFlavor::Network, NbtFlavor::LittleEndian];
+
+fn sample() -> NbtCompound {
+    parse_snbt(concat!(
+        "{b:-1b,s:258s,i:16909060,l:-2L,f:1.5f,d:-0.25d,str:\"text\",",
+        "bytes:[B;1b,2b],ints:[I;1,-1],longs:[L;1L,-1L],",
+        "list:[{a:1s},{b:[0.5f]}],empty:[],nested:{deeper:{}}}",
+    )).unwrap()
+}
+
+#[test]
+fn trees_round_trip_in_every_flavor() {
+    let compound = sample();
+    for flavor in FLAVORS {
+        let bytes = compound.write_with(Vec::new(), "root", flavor).unwrap();
+        let read = NbtCompound::read_with(&mut &bytes[..], flavor, NbtLimits::default()).unwrap();
+        assert_eq!(read, compound, "{flavor:?}");
+        assert_eq!(read.write_with(Vec::new(), "root", flavor).unwrap(), bytes, "{flavor:?}");
+    }
+}
+
+#[test]
+fn flavor_encodings() {
+    let mut compound = NbtCompound::new();
+    compound.insert("i", 0x01020304);
+
+    let java = compound.write_with(Vec::new(), "r", NbtFlavor::Java).unwrap();
+    assert_eq!(java, [10, 0, 1, b'r', 3, 0, 1, b'i', 1, 2, 3, 4, 0]);
+    // The root has no name at all, not even an empty one
+    let network = compound.write_with(Vec::new(), "r", NbtFlavor::Network).unwrap();
+    assert_eq!(network, [10, 3, 0, 1, b'i', 1, 2, 3, 4, 0]);
+    // String lengths are little endian as well as the numbers
+    let little = compound.write_with(Vec::new(), "r", NbtFlavor::LittleEndian).unwrap();
+    assert_eq!(little, [10, 1, 0, b'r', 3, 1, 0, b'i', 4, 3, 2, 1, 0]);
+
+    // Reading with the wrong flavor doesn't give back the same tree
+    let misread = NbtCompound::read_with(&mut &java[..], NbtFlavor::LittleEndian, NbtLimits::default());
+    assert_ne!(misread.ok(), Some(compound.clone()));
+    assert!(NbtCompound::read_with(&mut &network[..], NbtFlavor::Java, NbtLimits::default()).is_err());
+}
+
+#[test]
+fn streaming_writer_in_every_flavor() {
+    for flavor in FLAVORS {
+        let mut writer = NbtWriter::with_flavor(Vec::new(), flavor);
+        if flavor.has_root_name() {
+            writer.begin_compound("").unwrap();
+        } else {
+            writer.begin_compound_element().unwrap();
+        }
+        writer.begin_list("list", TagId::Long, 2).unwrap();
+        writer.write_leaf_element(&LeafTag::Long(-5)).unwrap();
+        writer.write_leaf_element(&LeafTag::Long(i64::MAX)).unwrap();
+        writer.end_list().unwrap();
+        writer.write_leaf("s", &LeafTag::String("é".into())).unwrap();
+        writer.end_compound().unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let read = NbtCompound::read_with(&mut &bytes[..], flavor, NbtLimits::default()).unwrap();
+        assert_eq!(read.to_string(), "{list:[-5L,9223372036854775807L],s:\"é\"}", "{flavor:?}");
+    }
+}
+
+#[test]
+fn deserializing_every_flavor() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Sample {
+        s: i16,
+        longs: Vec<i64>,
+        str: String,
+    }
+    let compound = sample();
+    for flavor in FLAVORS {
+        let bytes = compound.write_with(Vec::new(), "root", flavor).unwrap();
+        let sample: Sample = from_reader_with(&bytes[..], flavor, NbtLimits::default()).unwrap();
+        assert_eq!(sample, Sample { s: 258, longs: vec![1, -1], str: "text".into() }, "{flavor:?}");
+    }
+}
+
+#[test]
+fn root_naming_is_checked() {
+    let mut writer = NbtWriter::with_flavor(Vec::new(), NbtFlavor::Java);
+    let err = writer.begin_compound_element().unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::InvalidWriterState(_)), "{err}");
+
+    let mut writer = NbtWriter::with_flavor(Vec::new(), NbtFlavor::LittleEndian);
+    let err = writer.begin_compound_element().unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::InvalidWriterState(_)), "{err}");
+
+    let mut writer = NbtWriter::with_flavor(Vec::new(), NbtFlavor::Network);
+    let err = writer.begin_compound("root").unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::InvalidWriterState(_)), "{err}");
+    let err = writer.write_leaf("root", &LeafTag::Int(1)).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::InvalidNbtRoot(TagId::Int)), "{err}");
+    // Nothing gets written for a rejected root, so the writer can still be used
+    writer.begin_compound_element().unwrap();
+    writer.end_compound().unwrap();
+    assert_eq!(writer.finish().unwrap(), [10, 0]);
+}
@@ -0,0 +1,149 @@
+use mc_utils::nbt::{visit_nbt, visit_nbt_with, LeafTag, NbtCompound, NbtErrorKind, NbtFlavor, NbtLimits, NbtTreeVisitor, NbtWriter, TagId};
+
+fn sample_nbt() -> Vec<u8> {
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.write_leaf("name", &LeafTag::String("fuzz".into())).unwrap();
+    writer.write_leaf("bytes", &LeafTag::ByteArray(vec![1, 2, 3, 4])).unwrap();
+    writer.write_leaf("ints", &LeafTag::IntArray(vec![5, 6, 7])).unwrap();
+    writer.write_leaf("longs", &LeafTag::LongArray(vec![8, 9])).unwrap();
+    writer.begin_list("list", TagId::Compound, 2).unwrap();
+    for i in 0..2 {
+        writer.begin_compound_element().unwrap();
+        writer.write_leaf("i", &LeafTag::Int(i)).unwrap();
+        writer.begin_list("doubles", TagId::Double, 1).unwrap();
+        writer.write_leaf_element(&LeafTag::Double(1.5)).unwrap();
+        writer.end_list().unwrap();
+        writer.end_compound().unwrap();
+    }
+    writer.end_list().unwrap();
+    writer.end_compound().unwrap();
+    writer.finish().unwrap()
+}
+
+fn read_tree(bytes: &[u8]) -> mc_utils::nbt::Result<()> {
+    let mut visitor = NbtTreeVisitor::new();
+    visit_nbt(&mut &bytes[..], &mut visitor)
+}
+
+#[test]
+fn sample_reads_back() {
+    let bytes = sample_nbt();
+    let compound = NbtCompound::read(&mut &bytes[..]).unwrap();
+    assert_eq!(compound.len(), 5);
+}
+
+#[test]
+fn truncated_input_errors() {
+    let bytes = sample_nbt();
+    for len in 0..bytes.len() {
+        assert!(read_tree(&bytes[..len]).is_err(), "truncated at {len} should fail");
+    }
+}
+
+#[test]
+fn garbled_input_does_not_panic() {
+    let bytes = sample_nbt();
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut errors = 0;
+    for _ in 0..5000 {
+        let mut garbled = bytes.clone();
+        for _ in 0..(next() % 4 + 1) {
+            let index = (next() % garbled.len() as u64) as usize;
+            garbled[index] = next() as u8;
+        }
+        // Garbling a value rather than the structure still leaves valid NBT, which then has to
+        // write back out the same way every time
+        match NbtCompound::read(&mut &garbled[..]) {
+            Err(_) => errors += 1,
+            Ok(compound) => {
+                let written = compound.write(Vec::new(), "").unwrap();
+                let reread = NbtCompound::read(&mut &written[..]).unwrap();
+                assert_eq!(reread.write(Vec::new(), "").unwrap(), written);
+            }
+        }
+    }
+    assert!(errors > 2500, "only {errors} garbled inputs were rejected");
+}
+
+/// An empty-named root compound holding a single tag of `tag_id` named "a", up to the tag body
+fn root_with_tag(tag_id: TagId) -> Vec<u8> {
+    vec![TagId::Compound as u8, 0, 0, tag_id as u8, 0, 1, b'a']
+}
+
+#[test]
+fn negative_length_is_rejected() {
+    let mut bytes = root_with_tag(TagId::IntArray);
+    bytes.extend_from_slice(&(-1i32).to_be_bytes());
+    let err = read_tree(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::NegativeLength(-1)), "{err}");
+}
+
+#[test]
+fn huge_array_length_is_rejected() {
+    let mut bytes = root_with_tag(TagId::LongArray);
+    bytes.extend_from_slice(&i32::MAX.to_be_bytes());
+    let err = read_tree(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ArrayLengthLimitExceeded { .. }), "{err}");
+}
+
+#[test]
+fn huge_list_length_is_rejected_without_allocating() {
+    let mut bytes = root_with_tag(TagId::List);
+    bytes.push(TagId::Compound as u8);
+    bytes.extend_from_slice(&i32::MAX.to_be_bytes());
+    let err = read_tree(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ArrayLengthLimitExceeded { .. }), "{err}");
+}
+
+#[test]
+fn list_longer_than_the_byte_budget_is_rejected() {
+    // Within the length limit, but each compound takes at least a byte so a million of them can't
+    // fit in what's left of the budget
+    let mut bytes = root_with_tag(TagId::List);
+    bytes.push(TagId::Compound as u8);
+    bytes.extend_from_slice(&1_000_000i32.to_be_bytes());
+    bytes.resize(bytes.len() + 1024, TagId::End as u8);
+    let limits = NbtLimits {
+        max_total_bytes: 1 << 16,
+        ..NbtLimits::default()
+    };
+    let mut visitor = NbtTreeVisitor::new();
+    let err = visit_nbt_with(&mut &bytes[..], &mut visitor, NbtFlavor::Java, limits).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ByteLimitExceeded(_)), "{err}");
+    // Rejected at the list header, before any elements were read
+    assert_eq!(err.offset(), Some(bytes.len() as u64 - 1024));
+}
+
+#[test]
+fn deep_nesting_is_rejected() {
+    let mut bytes = root_with_tag(TagId::List);
+    for _ in 0..10_000 {
+        bytes.push(TagId::List as u8);
+        bytes.extend_from_slice(&1i32.to_be_bytes());
+    }
+    let err = read_tree(&bytes).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::DepthLimitExceeded(512)), "{err}");
+}
+
+#[test]
+fn byte_limit_is_enforced() {
+    let bytes = sample_nbt();
+    let limits = NbtLimits {
+        max_total_bytes: 32,
+        ..NbtLimits::default()
+    };
+    let mut visitor = NbtTreeVisitor::new();
+    let err = visit_nbt_with(&mut &bytes[..], &mut visitor, NbtFlavor::Java, limits).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ByteLimitExceeded(32)), "{err}");
+
+    let mut visitor = NbtTreeVisitor::new();
+    visit_nbt_with(&mut &bytes[..], &mut visitor, NbtFlavor::Java, NbtLimits::UNLIMITED).unwrap();
+}
@@ -0,0 +1,31 @@
+use mc_utils::nbt::{NbtCompound, NbtErrorKind, NbtList, NbtTag, TagId};
+
+#[test]
+fn list_elements_keep_their_type() {
+    let mut list = NbtList::new();
+    list.push(1).unwrap();
+    list.push(2).unwrap();
+    assert_eq!(list.elem_type(), TagId::Int);
+
+    let err = list.set(0, "text").unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ListTypeMismatch { expected: TagId::Int, found: TagId::String }), "{err}");
+    let err = list.get_mut(1).unwrap().set(3i64).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::ListTypeMismatch { expected: TagId::Int, found: TagId::Long }), "{err}");
+    assert!(list.set(2, 3).is_err());
+
+    assert_eq!(list.set(0, 10).unwrap(), NbtTag::Int(1));
+    for mut element in list.iter_mut() {
+        let val = element.as_int().unwrap();
+        element.set(val * 2).unwrap();
+    }
+    assert_eq!(list.iter().cloned().collect::<Vec<_>>(), vec![NbtTag::Int(20), NbtTag::Int(4)]);
+}
+
+#[test]
+fn nested_compounds_can_be_edited_in_place() {
+    let mut list = NbtList::new();
+    list.push(NbtCompound::new()).unwrap();
+    list.get_mut(0).unwrap().as_compound_mut().unwrap().insert("id", "minecraft:stone");
+    assert_eq!(list[0].as_compound().unwrap()["id"].as_string().unwrap(), "minecraft:stone");
+    assert!(list.get_mut(0).unwrap().as_list_mut().is_none());
+}
@@ -0,0 +1,204 @@
+use mc_utils::nbt::{visit_nbt, LeafTag, NbtError, NbtErrorKind, NbtPath, NbtVisitor, NbtWriter, Result, TagId, Visit};
+
+/// Logs every callback along with the path it was given
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+    /// Paths (as displayed) to skip
+    skip: Vec<&'static str>
+}
+
+impl NbtVisitor for Recorder {
+    fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> Result<()> {
+        self.events.push(format!("leaf {path} {val:?}"));
+        Ok(())
+    }
+
+    fn enter_tag(&mut self, tag_id: TagId, path: &NbtPath) -> Result<Visit> {
+        let path = path.to_string();
+        Ok(if self.skip.contains(&path.as_str()) {
+            self.events.push(format!("skip {path} {tag_id:?}"));
+            Visit::Skip
+        } else {
+            Visit::Continue
+        })
+    }
+
+    fn enter_compound(&mut self, path: &NbtPath) -> Result<()> {
+        self.events.push(format!("enter_compound {path}"));
+        Ok(())
+    }
+
+    fn exit_compound(&mut self, path: &NbtPath) -> Result<()> {
+        self.events.push(format!("exit_compound {path}"));
+        Ok(())
+    }
+
+    fn enter_list(&mut self, elem_type: TagId, len: usize, path: &NbtPath) -> Result<()> {
+        self.events.push(format!("enter_list {path} {elem_type:?} {len}"));
+        Ok(())
+    }
+
+    fn exit_list(&mut self, path: &NbtPath) -> Result<()> {
+        self.events.push(format!("exit_list {path}"));
+        Ok(())
+    }
+}
+
+fn record(bytes: &[u8], skip: &[&'static str]) -> Result<Vec<String>> {
+    let mut recorder = Recorder { events: Vec::new(), skip: skip.to_vec() };
+    visit_nbt(&mut &bytes[..], &mut recorder)?;
+    Ok(recorder.events)
+}
+
+/// `{a:1,c:{},e:[],l:[{x:1b},{}],n:[[["deep"]],[]],z:2}`, with `e` and `n[1]` lists of `TAG_End`
+fn sample() -> Vec<u8> {
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.write_leaf("a", &LeafTag::Int(1)).unwrap();
+    writer.begin_compound("c").unwrap();
+    writer.end_compound().unwrap();
+    writer.begin_list("e", TagId::End, 0).unwrap();
+    writer.end_list().unwrap();
+    writer.begin_list("l", TagId::Compound, 2).unwrap();
+    writer.begin_compound_element().unwrap();
+    writer.write_leaf("x", &LeafTag::Byte(1)).unwrap();
+    writer.end_compound().unwrap();
+    writer.begin_compound_element().unwrap();
+    writer.end_compound().unwrap();
+    writer.end_list().unwrap();
+    writer.begin_list("n", TagId::List, 2).unwrap();
+    writer.begin_list_element(TagId::List, 1).unwrap();
+    writer.begin_list_element(TagId::String, 1).unwrap();
+    writer.write_leaf_element(&LeafTag::String("deep".into())).unwrap();
+    writer.end_list().unwrap();
+    writer.end_list().unwrap();
+    writer.begin_list_element(TagId::End, 0).unwrap();
+    writer.end_list().unwrap();
+    writer.end_list().unwrap();
+    writer.write_leaf("z", &LeafTag::Int(2)).unwrap();
+    writer.end_compound().unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn structural_hooks_fire_in_order() {
+    assert_eq!(record(&sample(), &[]).unwrap(), vec![
+        "enter_compound ",
+        "leaf a Int(1)",
+        "enter_compound c",
+        "exit_compound c",
+        "enter_list e End 0",
+        "exit_list e",
+        "enter_list l Compound 2",
+        "enter_compound l[0]",
+        "leaf l[0].x Byte(1)",
+        "exit_compound l[0]",
+        "enter_compound l[1]",
+        "exit_compound l[1]",
+        "exit_list l",
+        "enter_list n List 2",
+        "enter_list n[0] List 1",
+        "enter_list n[0][0] String 1",
+        "leaf n[0][0][0] String(\"deep\")",
+        "exit_list n[0][0]",
+        "exit_list n[0]",
+        "enter_list n[1] End 0",
+        "exit_list n[1]",
+        "exit_list n",
+        "leaf z Int(2)",
+        "exit_compound ",
+    ]);
+}
+
+#[test]
+fn skipped_tags_are_never_seen() {
+    assert_eq!(record(&sample(), &["n", "l[0]", "c"]).unwrap(), vec![
+        "enter_compound ",
+        "leaf a Int(1)",
+        "skip c Compound",
+        "enter_list e End 0",
+        "exit_list e",
+        "enter_list l Compound 2",
+        "skip l[0] Compound",
+        "enter_compound l[1]",
+        "exit_compound l[1]",
+        "exit_list l",
+        "skip n List",
+        "leaf z Int(2)",
+        "exit_compound ",
+    ]);
+
+    // The string deep inside isn't decoded when skipped, so broken modified UTF-8 there goes unnoticed
+    let mut broken = sample();
+    let deep = broken.windows(4).position(|window| window == b"deep").unwrap();
+    broken[deep] = 0xff;
+    assert!(record(&broken, &[]).is_err());
+    assert_eq!(record(&broken, &["n"]).unwrap(), record(&sample(), &["n"]).unwrap());
+    assert_eq!(record(&broken, &["n[0]"]).unwrap().last().unwrap(), "exit_compound ");
+}
+
+/// Errors out on the first leaf it's given
+struct Failer;
+
+impl NbtVisitor for Failer {
+    fn visit_leaf(&mut self, _val: LeafTag, _path: &NbtPath) -> Result<()> {
+        Err(NbtError::custom("no leaves allowed"))
+    }
+}
+
+fn chunk_like() -> Vec<u8> {
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("").unwrap();
+    writer.begin_compound("Level").unwrap();
+    writer.begin_list("Sections", TagId::Compound, 4).unwrap();
+    for _ in 0..3 {
+        writer.begin_compound_element().unwrap();
+        writer.end_compound().unwrap();
+    }
+    writer.begin_compound_element().unwrap();
+    writer.begin_list("with space", TagId::Int, 0).unwrap();
+    writer.end_list().unwrap();
+    writer.write_leaf("Blocks", &LeafTag::ByteArray(vec![1; 4096])).unwrap();
+    writer.end_compound().unwrap();
+    writer.end_list().unwrap();
+    writer.end_compound().unwrap();
+    writer.end_compound().unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn paths_display_like_data_commands() {
+    let events = record(&chunk_like(), &[]).unwrap();
+    assert!(events.contains(&"enter_list Level.Sections[3].\"with space\" Int 0".to_string()), "{events:?}");
+    assert!(events.iter().any(|event| event.starts_with("leaf Level.Sections[3].Blocks ByteArray")), "{events:?}");
+
+    // The root's own name is left out, and a dot in a name can't be mistaken for a separator
+    let mut writer = NbtWriter::new(Vec::new());
+    writer.begin_compound("root").unwrap();
+    writer.write_leaf("a.b", &LeafTag::Int(1)).unwrap();
+    writer.end_compound().unwrap();
+    let events = record(&writer.finish().unwrap(), &[]).unwrap();
+    assert_eq!(events[1], "leaf \"a.b\" Int(1)");
+
+    assert_eq!(NbtPath::default().to_string(), "");
+}
+
+#[test]
+fn errors_carry_the_path_and_offset() {
+    let bytes = chunk_like();
+    // Cut off part way through the Blocks array
+    let truncated = &bytes[..bytes.len() - 2000];
+    let err = record(truncated, &[]).unwrap_err();
+    assert_eq!(err.path().to_string(), "Level.Sections[3].Blocks");
+    assert_eq!(err.offset(), Some(truncated.len() as u64));
+    assert!(err.to_string().contains(" at Level.Sections[3].Blocks"), "{err}");
+
+    // Errors from the visitor get the location of the tag it was handed
+    let blocks_end = bytes.len() - 3;
+    let err = visit_nbt(&mut &bytes[..], &mut Failer).unwrap_err();
+    assert!(matches!(err.kind(), NbtErrorKind::Custom(_)), "{err}");
+    assert_eq!(err.path().to_string(), "Level.Sections[3].Blocks");
+    assert_eq!(err.offset(), Some(blocks_end as u64));
+    assert_eq!(err.to_string(), format!("Deserialization Error no leaves allowed at Level.Sections[3].Blocks (byte {blocks_end})"));
+}
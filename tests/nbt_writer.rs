@@ -0,0 +1,166 @@
+use java_string::JavaString;
+use mc_utils::nbt::{visit_nbt, LeafTag, NbtErrorKind, NbtPath, NbtPathElement, NbtVisitor, NbtWriter, Result, TagId};
+
+/// Feeds everything [`visit_nbt`] reads straight back into an [`NbtWriter`]
+struct Copier {
+    writer: NbtWriter<Vec<u8>>,
+}
+
+impl Copier {
+    fn name(path: &NbtPath) -> Option<&JavaString> {
+        match path.peek() {
+            Some(NbtPathElement::Element(name)) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl NbtVisitor for Copier {
+    fn visit_leaf(&mut self, val: LeafTag, path: &NbtPath) -> Result<()> {
+        match Self::name(path) {
+            Some(name) => self.writer.write_leaf(name, &val),
+            None => self.writer.write_leaf_element(&val),
+        }
+    }
+
+    fn enter_compound(&mut self, path: &NbtPath) -> Result<()> {
+        match Self::name(path) {
+            Some(name) => self.writer.begin_compound(name),
+            None => self.writer.begin_compound_element(),
+        }
+    }
+
+    fn exit_compound(&mut self, _path: &NbtPath) -> Result<()> {
+        self.writer.end_compound()
+    }
+
+    fn enter_list(&mut self, elem_type: TagId, len: usize, path: &NbtPath) -> Result<()> {
+        match Self::name(path) {
+            Some(name) => self.writer.begin_list(name, elem_type, len),
+            None => self.writer.begin_list_element(elem_type, len),
+        }
+    }
+
+    fn exit_list(&mut self, _path: &NbtPath) -> Result<()> {
+        self.writer.end_list()
+    }
+}
+
+fn copy(bytes: &[u8]) -> Vec<u8> {
+    let mut copier = Copier { writer: NbtWriter::new(Vec::new()) };
+    visit_nbt(&mut &bytes[..], &mut copier).unwrap();
+    copier.writer.finish().unwrap()
+}
+
+fn push_name(bytes: &mut Vec<u8>, name: &[u8]) {
+    bytes.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(name);
+}
+
+fn push_string(bytes: &mut Vec<u8>, name: &[u8], val: &[u8]) {
+    bytes.push(TagId::String as u8);
+    push_name(bytes, name);
+    push_name(bytes, val);
+}
+
+/// A hand written root compound, so the bytes don't depend on the writer being tested
+fn sample_nbt() -> Vec<u8> {
+    let mut bytes = vec![TagId::Compound as u8];
+    push_name(&mut bytes, b"root");
+
+    push_string(&mut bytes, b"plain", b"hello");
+    // A lone surrogate, only representable in modified UTF-8 (as saved by save-state books)
+    push_string(&mut bytes, b"lone", &[b'a', 0xed, 0xa0, 0x80, b'b']);
+    // A supplementary character as a CESU-8 surrogate pair, and an embedded null as 0xc0 0x80
+    push_string(&mut bytes, b"pair", &[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, 0xc0, 0x80]);
+    // Keys go through the same encoding
+    push_string(&mut bytes, &[0xed, 0xb0, 0x80], b"key");
+
+    bytes.push(TagId::List as u8);
+    push_name(&mut bytes, b"list");
+    bytes.push(TagId::Compound as u8);
+    bytes.extend_from_slice(&2i32.to_be_bytes());
+    for i in 0..2i32 {
+        bytes.push(TagId::Int as u8);
+        push_name(&mut bytes, b"i");
+        bytes.extend_from_slice(&i.to_be_bytes());
+        bytes.push(TagId::List as u8);
+        push_name(&mut bytes, b"nested");
+        bytes.push(TagId::String as u8);
+        bytes.extend_from_slice(&1i32.to_be_bytes());
+        push_name(&mut bytes, &[0xed, 0xa0, 0x81]);
+        bytes.push(TagId::End as u8);
+    }
+
+    bytes.push(TagId::List as u8);
+    push_name(&mut bytes, b"empty");
+    bytes.push(TagId::End as u8);
+    bytes.extend_from_slice(&0i32.to_be_bytes());
+
+    bytes.push(TagId::LongArray as u8);
+    push_name(&mut bytes, b"longs");
+    bytes.extend_from_slice(&2i32.to_be_bytes());
+    bytes.extend_from_slice(&i64::MIN.to_be_bytes());
+    bytes.extend_from_slice(&(-1i64).to_be_bytes());
+
+    bytes.push(TagId::Compound as u8);
+    push_name(&mut bytes, b"");
+    bytes.push(TagId::Double as u8);
+    push_name(&mut bytes, b"d");
+    bytes.extend_from_slice(&f64::NAN.to_be_bytes());
+    bytes.push(TagId::End as u8);
+
+    bytes.push(TagId::End as u8);
+    bytes
+}
+
+#[test]
+fn visited_nbt_writes_back_identically() {
+    let bytes = sample_nbt();
+    assert_eq!(copy(&bytes), bytes);
+}
+
+#[test]
+fn non_utf8_strings_survive() {
+    let bytes = sample_nbt();
+    let mut visitor = mc_utils::nbt::NbtTreeVisitor::new();
+    visit_nbt(&mut &bytes[..], &mut visitor).unwrap();
+    let root = visitor.into_root();
+
+    let lone = root["lone"].as_string().unwrap();
+    assert!(lone.as_str().is_err());
+    assert_eq!(lone.to_modified_utf8().as_ref(), &[b'a', 0xed, 0xa0, 0x80, b'b']);
+    assert_eq!(root["pair"].as_string().unwrap().as_str().unwrap(), "\u{1f600}\0");
+
+    let written = root.write(Vec::new(), "root").unwrap();
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn failed_writes_leave_nothing_behind() {
+    let long = JavaString::from("x".repeat(70000));
+    let mut writer = NbtWriter::new(Vec::new());
+    assert!(matches!(writer.begin_compound(&long).unwrap_err().kind(), NbtErrorKind::StringTooLong(70000)));
+    writer.begin_compound("root").unwrap();
+    assert!(matches!(writer.write_leaf(&long, &LeafTag::Int(1)).unwrap_err().kind(), NbtErrorKind::StringTooLong(70000)));
+    assert!(matches!(writer.begin_list(&long, TagId::Int, 0).unwrap_err().kind(), NbtErrorKind::StringTooLong(70000)));
+    assert!(matches!(writer.begin_compound(&long).unwrap_err().kind(), NbtErrorKind::StringTooLong(70000)));
+    assert!(matches!(writer.write_leaf("value", &LeafTag::String(long.clone())).unwrap_err().kind(), NbtErrorKind::StringTooLong(70000)));
+    assert!(matches!(writer.begin_list("list", TagId::End, 1).unwrap_err().kind(), NbtErrorKind::InvalidNbtEndTag));
+    writer.begin_list("list", TagId::String, 1).unwrap();
+    assert!(writer.write_leaf_element(&LeafTag::String(long)).is_err());
+    writer.write_leaf_element(&LeafTag::String("fits".into())).unwrap();
+    writer.end_list().unwrap();
+    writer.write_leaf("ok", &LeafTag::Int(1)).unwrap();
+    writer.end_compound().unwrap();
+
+    // The same as if the failed calls were never made
+    let mut expected = NbtWriter::new(Vec::new());
+    expected.begin_compound("root").unwrap();
+    expected.begin_list("list", TagId::String, 1).unwrap();
+    expected.write_leaf_element(&LeafTag::String("fits".into())).unwrap();
+    expected.end_list().unwrap();
+    expected.write_leaf("ok", &LeafTag::Int(1)).unwrap();
+    expected.end_compound().unwrap();
+    assert_eq!(writer.finish().unwrap(), expected.finish().unwrap());
+}
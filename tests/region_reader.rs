@@ -0,0 +1,152 @@
+mod common;
+
+use std::cell::Cell;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::rc::Rc;
+use mc_utils::positions::{ChunkPos, RegionPos};
+use mc_utils::region::{ChunkInfo, Region};
+
+/// Counts the seeks made through it, every chunk read from the file starts with one
+struct CountingReader {
+    inner: Cursor<Vec<u8>>,
+    seeks: Rc<Cell<usize>>
+}
+
+impl Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Seek for CountingReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.seeks.set(self.seeks.get() + 1);
+        self.inner.seek(pos)
+    }
+}
+
+/// A region with the given payloads one after the other from sector 2, each chunk `i` in slot
+/// (`i`, 0) with timestamp `i + 100`
+fn region_bytes(payloads: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = vec![0u8; 8192];
+    for (index, payload) in payloads.iter().enumerate() {
+        let offset = data.len() / 4096;
+        let sector_count = payload.len().div_ceil(4096);
+        data[index * 4..index * 4 + 4].copy_from_slice(&((offset as u32) << 8 | sector_count as u32).to_be_bytes());
+        data[4096 + index * 4..4096 + index * 4 + 4].copy_from_slice(&(index as u32 + 100).to_be_bytes());
+        data.extend_from_slice(payload);
+        data.resize(data.len().div_ceil(4096) * 4096, 0);
+    }
+    data
+}
+
+/// A region of 20 chunks, each with its index as its `LastUpdate`
+fn counting_region() -> (Region<CountingReader>, Rc<Cell<usize>>) {
+    let payloads: Vec<_> = (0..20).map(|x| common::snbt_payload(&format!("{{Level:{{xPos:{x},zPos:0,LastUpdate:{x}L}}}}"))).collect();
+    let seeks = Rc::new(Cell::new(0));
+    let reader = CountingReader { inner: Cursor::new(region_bytes(&payloads)), seeks: seeks.clone() };
+    (Region::open(reader).unwrap(), seeks)
+}
+
+#[test]
+fn chunk_cache_hits() {
+    let (mut region, seeks) = counting_region();
+    let before = seeks.get();
+    assert_eq!(region.get_chunk(ChunkPos::new(3, 0)).unwrap().unwrap().last_update, 3);
+    assert_eq!(seeks.get(), before + 1);
+    // The second time it comes from the cache
+    assert_eq!(region.get_chunk(ChunkPos::new(3, 0)).unwrap().unwrap().last_update, 3);
+    assert_eq!(seeks.get(), before + 1);
+
+    // read_chunk always goes to the file
+    assert_eq!(region.read_chunk(ChunkPos::new(3, 0)).unwrap().unwrap().last_update, 3);
+    assert_eq!(seeks.get(), before + 2);
+
+    // Missing chunks aren't read or cached
+    let before = seeks.get();
+    assert!(region.get_chunk(ChunkPos::new(30, 30)).unwrap().is_none());
+    assert_eq!(seeks.get(), before);
+}
+
+#[test]
+fn chunk_cache_evicts_the_least_recently_used() {
+    let (mut region, seeks) = counting_region();
+    // Fill the default 16 entries
+    for x in 0..16 {
+        region.get_chunk(ChunkPos::new(x, 0)).unwrap();
+    }
+    // Chunk 0 becomes the most recently used, so the 17th chunk evicts chunk 1 instead
+    let before = seeks.get();
+    region.get_chunk(ChunkPos::new(0, 0)).unwrap();
+    region.get_chunk(ChunkPos::new(16, 0)).unwrap();
+    assert_eq!(seeks.get(), before + 1);
+    let before = seeks.get();
+    for x in [0, 2, 15, 16] {
+        region.get_chunk(ChunkPos::new(x, 0)).unwrap();
+    }
+    assert_eq!(seeks.get(), before);
+    region.get_chunk(ChunkPos::new(1, 0)).unwrap();
+    assert_eq!(seeks.get(), before + 1);
+
+    // Shrinking the cache drops the least recently used straight away: only 1 and 16 are left
+    region.set_cache_size(2);
+    let before = seeks.get();
+    region.get_chunk(ChunkPos::new(16, 0)).unwrap();
+    region.get_chunk(ChunkPos::new(1, 0)).unwrap();
+    assert_eq!(seeks.get(), before);
+    region.get_chunk(ChunkPos::new(15, 0)).unwrap();
+    assert_eq!(seeks.get(), before + 1);
+    // 16 was evicted for 15
+    region.get_chunk(ChunkPos::new(16, 0)).unwrap();
+    assert_eq!(seeks.get(), before + 2);
+
+    // And it's never smaller than one chunk
+    region.set_cache_size(0);
+    let before = seeks.get();
+    region.get_chunk(ChunkPos::new(16, 0)).unwrap();
+    assert_eq!(seeks.get(), before);
+}
+
+/// `len` bytes of junk after the length and `compression_type`, nothing here decodes it
+fn raw(len: usize, compression_type: u8) -> Vec<u8> {
+    let mut payload = ((len + 1) as u32).to_be_bytes().to_vec();
+    payload.push(compression_type);
+    payload.resize(len + 5, 0xaa);
+    payload
+}
+
+#[test]
+fn chunk_info_and_payload_length() {
+    let mut region = Region::open(Cursor::new(region_bytes(&[raw(10, 3), raw(5000, 2), raw(0, 1)]))).unwrap();
+    assert_eq!(region.payload_length(ChunkPos::new(0, 0)).unwrap(), Some(11));
+    assert_eq!(region.payload_length(ChunkPos::new(1, 0)).unwrap(), Some(5001));
+    assert_eq!(region.payload_length(ChunkPos::new(2, 0)).unwrap(), Some(1));
+    assert_eq!(region.payload_length(ChunkPos::new(3, 0)).unwrap(), None);
+
+    let infos: Vec<_> = region.chunk_info_iter().map(Result::unwrap).collect();
+    assert_eq!(infos, vec![
+        ChunkInfo { pos: ChunkPos::new(0, 0), timestamp: 100, sectors: 2..3, payload_length: 11, compression_type: 3 },
+        ChunkInfo { pos: ChunkPos::new(1, 0), timestamp: 101, sectors: 3..5, payload_length: 5001, compression_type: 2 },
+        ChunkInfo { pos: ChunkPos::new(2, 0), timestamp: 102, sectors: 5..6, payload_length: 1, compression_type: 1 }
+    ]);
+
+    // Positions are absolute once the region knows where it is, the lookups take either
+    region.set_pos(RegionPos::new(-1, 2));
+    let positions: Vec<_> = region.chunk_info_iter().map(|info| info.unwrap().pos).collect();
+    assert_eq!(positions, vec![ChunkPos::new(-32, 64), ChunkPos::new(-31, 64), ChunkPos::new(-30, 64)]);
+    assert_eq!(region.payload_length(ChunkPos::new(-31, 64)).unwrap(), Some(5001));
+    assert_eq!(region.payload_length(ChunkPos::new(1, 0)).unwrap(), Some(5001));
+}
+
+#[test]
+fn chunk_info_past_the_end_of_the_file() {
+    let mut data = region_bytes(&[raw(10, 3), raw(10, 3)]);
+    // Point the second chunk past the end, its entry is an error but the others still come through
+    data[4..8].copy_from_slice(&(9u32 << 8 | 1).to_be_bytes());
+    let mut region = Region::open(Cursor::new(data)).unwrap();
+    let infos: Vec<_> = region.chunk_info_iter().collect();
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[0].as_ref().unwrap().payload_length, 11);
+    assert!(infos[1].is_err());
+    assert!(region.payload_length(ChunkPos::new(1, 0)).is_err());
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use std::io::Cursor;
+use mc_utils::chunk::ChunkErrorKind;
+use mc_utils::positions::{ChunkPos, RegionPos};
+use mc_utils::region::{Region, RegionIssue};
+
+/// A chunk payload that decodes to a chunk claiming to be at (`x`, `z`)
+fn chunk_payload(x: i32, z: i32) -> Vec<u8> {
+    common::snbt_payload(&format!("{{Level:{{xPos:{x},zPos:{z}}}}}"))
+}
+
+/// The header entry for slot (`x`, `z`) and the payload to put at its offset
+struct Entry {
+    slot: (i32, i32),
+    offset: u32,
+    sector_count: u8,
+    payload: Vec<u8>
+}
+
+fn entry(slot: (i32, i32), offset: u32, sector_count: u8, payload: Vec<u8>) -> Entry {
+    Entry { slot, offset, sector_count, payload }
+}
+
+/// A region `file_sectors` long with the given entries. Payloads past the end aren't written.
+fn build_region(entries: &[Entry], file_sectors: usize) -> Region<Cursor<Vec<u8>>> {
+    let mut data = vec![0u8; file_sectors * 4096];
+    for entry in entries {
+        let index = Region::get_chunk_index(ChunkPos::new(entry.slot.0, entry.slot.1));
+        let offset = entry.offset.to_be_bytes();
+        data[index * 4..index * 4 + 4].copy_from_slice(&[offset[1], offset[2], offset[3], entry.sector_count]);
+        data[4096 + index * 4..4096 + index * 4 + 4].copy_from_slice(&1u32.to_be_bytes());
+        let start = entry.offset as usize * 4096;
+        if start >= 8192 && start + entry.payload.len() <= data.len() {
+            data[start..start + entry.payload.len()].copy_from_slice(&entry.payload);
+        }
+    }
+    Region::open(Cursor::new(data)).unwrap()
+}
+
+/// The issues as strings so they can be compared, decode errors reduced to their position
+fn issues(region: &mut Region<Cursor<Vec<u8>>>) -> Vec<String> {
+    region.verify().unwrap().iter().map(|issue| match issue {
+        RegionIssue::DecodeFailed(err) => format!("DecodeFailed({:?})", err.pos()),
+        _ => format!("{issue:?}")
+    }).collect()
+}
+
+fn pos(x: i32, z: i32) -> ChunkPos {
+    ChunkPos::new(x, z)
+}
+
+#[test]
+fn clean_region_has_no_issues() {
+    let mut region = build_region(&[entry((0, 0), 2, 1, chunk_payload(0, 0)), entry((31, 31), 3, 2, chunk_payload(31, 31))], 5);
+    assert!(issues(&mut region).is_empty());
+    assert!(issues(&mut build_region(&[], 2)).is_empty());
+}
+
+#[test]
+fn header_overlap() {
+    // The "chunk" in sector 1 is the timestamp table, whose first entry reads as a length of 0 and
+    // a compression type of 0
+    let mut region = build_region(&[entry((2, 0), 1, 1, Vec::new()), entry((3, 0), 2, 1, chunk_payload(3, 0))], 3);
+    assert_eq!(issues(&mut region), vec![
+        format!("{:?}", RegionIssue::HeaderOverlap(pos(2, 0))),
+        format!("{:?}", RegionIssue::UnknownCompression(pos(2, 0), 0))
+    ]);
+}
+
+#[test]
+fn overlap() {
+    // The first chunk claims sector 3 too, though its payload only needs sector 2
+    let mut region = build_region(&[entry((0, 0), 2, 2, chunk_payload(0, 0)), entry((1, 0), 3, 1, chunk_payload(1, 0)),
+                                    entry((2, 0), 4, 1, chunk_payload(2, 0))], 5);
+    assert_eq!(issues(&mut region), vec![format!("{:?}", RegionIssue::Overlap(pos(0, 0), pos(1, 0)))]);
+
+    // One chunk covering two others is reported against both
+    let mut region = build_region(&[entry((0, 0), 2, 3, chunk_payload(0, 0)), entry((1, 0), 3, 1, chunk_payload(1, 0)),
+                                    entry((2, 0), 4, 1, chunk_payload(2, 0))], 5);
+    assert_eq!(issues(&mut region), vec![
+        format!("{:?}", RegionIssue::Overlap(pos(0, 0), pos(1, 0))),
+        format!("{:?}", RegionIssue::Overlap(pos(0, 0), pos(2, 0)))
+    ]);
+}
+
+#[test]
+fn no_sectors() {
+    // The middle entry points inside the first chunk's sectors, but with a sector count of 0 it
+    // doesn't actually claim any of them
+    let mut region = build_region(&[entry((0, 0), 2, 2, chunk_payload(0, 0)), entry((1, 0), 3, 0, chunk_payload(1, 0)),
+                                    entry((2, 0), 4, 1, chunk_payload(2, 0))], 5);
+    assert_eq!(issues(&mut region), vec![format!("{:?}", RegionIssue::NoSectors(pos(1, 0), 3))]);
+    assert_eq!(region.verify().unwrap()[0].to_string(), "Chunk (1, 0) is at sector 3 but has no sectors");
+
+    // Even past the end of the file
+    let mut region = build_region(&[entry((5, 5), 40, 0, Vec::new())], 2);
+    assert_eq!(issues(&mut region), vec![format!("{:?}", RegionIssue::NoSectors(pos(5, 5), 40))]);
+}
+
+#[test]
+fn past_eof() {
+    let mut region = build_region(&[entry((0, 0), 2, 1, chunk_payload(0, 0)), entry((5, 5), 10, 1, chunk_payload(5, 5)),
+                                    entry((6, 5), 2, 2, Vec::new())], 3);
+    // The last one starts in the file but runs past its end, and overlaps the first
+    assert_eq!(issues(&mut region), vec![
+        format!("{:?}", RegionIssue::Overlap(pos(0, 0), pos(6, 5))),
+        format!("{:?}", RegionIssue::PastEof(pos(5, 5), 10..11)),
+        format!("{:?}", RegionIssue::PastEof(pos(6, 5), 2..4))
+    ]);
+}
+
+#[test]
+fn length_too_large() {
+    let mut payload = chunk_payload(0, 0);
+    payload[..4].copy_from_slice(&10000u32.to_be_bytes());
+    let mut region = build_region(&[entry((0, 0), 2, 1, payload), entry((1, 0), 3, 1, chunk_payload(1, 0))], 4);
+    assert_eq!(issues(&mut region), vec![format!("{:?}", RegionIssue::LengthTooLarge(pos(0, 0), 10000, 1))]);
+}
+
+#[test]
+fn unknown_compression() {
+    let mut payload = chunk_payload(0, 0);
+    payload[4] = 7;
+    let mut region = build_region(&[entry((0, 0), 2, 1, payload), entry((1, 0), 3, 1, chunk_payload(1, 0))], 4);
+    assert_eq!(issues(&mut region), vec![format!("{:?}", RegionIssue::UnknownCompression(pos(0, 0), 7))]);
+}
+
+#[test]
+fn decode_failed() {
+    // Uncompressed, but not NBT
+    let mut payload = 11u32.to_be_bytes().to_vec();
+    payload.push(3);
+    payload.extend_from_slice(b"not nbt!!!");
+    let mut region = build_region(&[entry((4, 0), 2, 1, payload), entry((1, 0), 3, 1, chunk_payload(1, 0))], 4);
+    let found = region.verify().unwrap();
+    assert_eq!(found.len(), 1);
+    let RegionIssue::DecodeFailed(err) = &found[0] else {
+        panic!("{:?}", found[0]);
+    };
+    assert_eq!(err.pos(), Some(pos(4, 0)));
+    assert!(matches!(err.kind(), ChunkErrorKind::NbtError(_)), "{err}");
+}
+
+#[test]
+fn wrong_position() {
+    // Stored in (1, 0) but says it's (5, 0)
+    let entries = [entry((0, 0), 2, 1, chunk_payload(32, 0)), entry((1, 0), 3, 1, chunk_payload(5, 0))];
+    let mut unplaced = build_region(&entries, 4);
+    assert_eq!(issues(&mut unplaced), vec![format!("{:?}", RegionIssue::WrongPosition(pos(1, 0), pos(5, 0)))]);
+
+    // Once the region knows it's r.1.0 the positions are absolute, and (32, 0) is where it belongs
+    let mut placed = build_region(&entries, 4);
+    placed.set_pos(RegionPos::new(1, 0));
+    assert_eq!(issues(&mut placed), vec![format!("{:?}", RegionIssue::WrongPosition(pos(33, 0), pos(5, 0)))]);
+
+    // The right slot in the wrong region
+    let mut wrong_region = build_region(&entries, 4);
+    wrong_region.set_pos(RegionPos::new(0, 0));
+    assert_eq!(issues(&mut wrong_region), vec![
+        format!("{:?}", RegionIssue::WrongPosition(pos(0, 0), pos(32, 0))),
+        format!("{:?}", RegionIssue::WrongPosition(pos(1, 0), pos(5, 0)))
+    ]);
+}
+
+#[test]
+fn every_issue_at_once() {
+    let mut long = chunk_payload(3, 0);
+    long[..4].copy_from_slice(&5000u32.to_be_bytes());
+    let mut gzip_claimed = chunk_payload(4, 0);
+    gzip_claimed[4] = 9;
+    let mut region = build_region(&[
+        entry((0, 0), 1, 1, Vec::new()),
+        entry((1, 0), 2, 2, chunk_payload(1, 0)),
+        entry((2, 0), 3, 1, chunk_payload(2, 0)),
+        entry((3, 0), 4, 1, long),
+        entry((4, 0), 5, 1, gzip_claimed),
+        entry((5, 0), 6, 1, chunk_payload(9, 9)),
+        entry((6, 0), 50, 3, Vec::new())
+    ], 7);
+    region.set_pos(RegionPos::new(0, 0));
+    assert_eq!(issues(&mut region), vec![
+        format!("{:?}", RegionIssue::HeaderOverlap(pos(0, 0))),
+        format!("{:?}", RegionIssue::Overlap(pos(1, 0), pos(2, 0))),
+        format!("{:?}", RegionIssue::UnknownCompression(pos(0, 0), 0)),
+        format!("{:?}", RegionIssue::LengthTooLarge(pos(3, 0), 5000, 1)),
+        format!("{:?}", RegionIssue::UnknownCompression(pos(4, 0), 9)),
+        format!("{:?}", RegionIssue::WrongPosition(pos(5, 0), pos(9, 9))),
+        format!("{:?}", RegionIssue::PastEof(pos(6, 0), 50..53))
+    ]);
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::positions::{ChunkPos, RegionPos};
+use mc_utils::region::{Region, RegionWriter};
+use mc_utils::world::{Dimension, World};
+
+/// A chunk payload in the format [`Region::read_raw`] returns, `len` bytes of `fill` after the
+/// compression type. The bytes don't have to be valid NBT for the raw functions.
+fn raw(len: usize, fill: u8) -> Vec<u8> {
+    let mut payload = ((len + 1) as u32).to_be_bytes().to_vec();
+    payload.push(3);
+    payload.resize(len + 5, fill);
+    payload
+}
+
+/// The payload that fills exactly `sectors` sectors
+fn raw_sectors(sectors: usize, fill: u8) -> Vec<u8> {
+    raw(sectors * 4096 - 5, fill)
+}
+
+fn sectors(writer: &RegionWriter, pos: (i32, i32)) -> (u32, u8) {
+    let location = writer.header().location(ChunkPos::new(pos.0, pos.1));
+    (location.offset(), location.sector_count())
+}
+
+/// Reopens the file and checks every chunk in `expected` reads back exactly
+fn assert_contents(path: &Path, expected: &[((i32, i32), &[u8])]) {
+    let mut region = Region::open(File::open(path).unwrap()).unwrap();
+    for &((x, z), payload) in expected {
+        assert_eq!(region.read_raw(ChunkPos::new(x, z)).unwrap().as_deref(), Some(payload), "chunk ({x}, {z})");
+    }
+    let present = (0..1024).filter(|&index| region.header.location(Region::get_chunk_offset(index)).is_present()).count();
+    assert_eq!(present, expected.len());
+}
+
+fn file_len(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+#[test]
+fn allocates_sectors_after_the_header() {
+    let dir = common::test_dir("allocate");
+    let path = dir.join("r.0.0.mca");
+    let (a, b, c) = (raw(10, 1), raw_sectors(2, 2), raw(4092, 3));
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    writer.write_raw(ChunkPos::new(0, 0), &a).unwrap();
+    writer.write_raw(ChunkPos::new(1, 0), &b).unwrap();
+    writer.write_raw(ChunkPos::new(31, 31), &c).unwrap();
+    assert_eq!(sectors(&writer, (0, 0)), (2, 1));
+    assert_eq!(sectors(&writer, (1, 0)), (3, 2));
+    // One byte more than a sector holds
+    assert_eq!(sectors(&writer, (31, 31)), (5, 2));
+    writer.set_timestamp(ChunkPos::new(0, 0), 1234);
+    writer.flush().unwrap();
+
+    assert_eq!(file_len(&path), 7 * 4096);
+    assert_contents(&path, &[((0, 0), &a), ((1, 0), &b), ((31, 31), &c)]);
+    let region = Region::open(File::open(&path).unwrap()).unwrap();
+    assert_eq!(region.timestamp(ChunkPos::new(0, 0)), Some(1234));
+    assert!(region.timestamp(ChunkPos::new(1, 0)).is_some_and(|timestamp| timestamp > 0));
+    common::cleanup(&dir);
+}
+
+#[test]
+fn reuses_freed_sectors() {
+    let dir = common::test_dir("reuse");
+    let path = dir.join("r.0.0.mca");
+    let (a, b, c) = (raw_sectors(1, 1), raw_sectors(2, 2), raw_sectors(1, 3));
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    writer.write_raw(ChunkPos::new(0, 0), &a).unwrap();
+    writer.write_raw(ChunkPos::new(1, 0), &b).unwrap();
+    writer.write_raw(ChunkPos::new(2, 0), &c).unwrap();
+    writer.flush().unwrap();
+
+    // Sectors 3 and 4 are freed, and then filled from the front
+    let mut writer = RegionWriter::open(&path).unwrap();
+    writer.delete_chunk(ChunkPos::new(1, 0));
+    let (d, e, f) = (raw(100, 4), raw_sectors(2, 5), raw(200, 6));
+    writer.write_raw(ChunkPos::new(3, 0), &d).unwrap();
+    assert_eq!(sectors(&writer, (3, 0)), (3, 1));
+    // Doesn't fit in the one sector gap left at 4
+    writer.write_raw(ChunkPos::new(4, 0), &e).unwrap();
+    assert_eq!(sectors(&writer, (4, 0)), (6, 2));
+    writer.write_raw(ChunkPos::new(5, 0), &f).unwrap();
+    assert_eq!(sectors(&writer, (5, 0)), (4, 1));
+    writer.flush().unwrap();
+
+    assert_eq!(file_len(&path), 8 * 4096);
+    assert_contents(&path, &[((0, 0), &a), ((2, 0), &c), ((3, 0), &d), ((4, 0), &e), ((5, 0), &f)]);
+    common::cleanup(&dir);
+}
+
+#[test]
+fn grows_a_chunk_past_its_sectors() {
+    let dir = common::test_dir("grow");
+    let path = dir.join("r.0.0.mca");
+    let (a, b) = (raw(10, 1), raw(10, 2));
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    writer.write_raw(ChunkPos::new(0, 0), &a).unwrap();
+    writer.write_raw(ChunkPos::new(0, 1), &b).unwrap();
+    writer.flush().unwrap();
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    let grown = raw_sectors(3, 7);
+    writer.write_raw(ChunkPos::new(0, 0), &grown).unwrap();
+    // Moved past the chunk after it, leaving its old sector free
+    assert_eq!(sectors(&writer, (0, 0)), (4, 3));
+    let small = raw(50, 8);
+    writer.write_raw(ChunkPos::new(0, 2), &small).unwrap();
+    assert_eq!(sectors(&writer, (0, 2)), (2, 1));
+    // Shrinking in place frees the sectors it no longer needs
+    let shrunk = raw(20, 9);
+    writer.write_raw(ChunkPos::new(0, 0), &shrunk).unwrap();
+    assert_eq!(sectors(&writer, (0, 0)), (4, 1));
+    writer.flush().unwrap();
+
+    assert_eq!(file_len(&path), 7 * 4096);
+    assert_contents(&path, &[((0, 0), &shrunk), ((0, 1), &b), ((0, 2), &small)]);
+    common::cleanup(&dir);
+}
+
+#[test]
+fn keeps_a_chunk_in_a_partial_last_sector() {
+    let dir = common::test_dir("partial");
+    let path = dir.join("r.0.0.mca");
+    let (a, b) = (raw_sectors(1, 1), raw(300, 2));
+
+    // As some older versions saved it: the last chunk isn't padded out to a full sector
+    let mut data = vec![0u8; 8192];
+    data[..4].copy_from_slice(&[0, 0, 2, 1]);
+    data[4..8].copy_from_slice(&[0, 0, 3, 1]);
+    data.extend_from_slice(&a);
+    data.extend_from_slice(&b);
+    std::fs::write(&path, &data).unwrap();
+    assert_contents(&path, &[((0, 0), &a), ((1, 0), &b)]);
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    let c = raw(10, 3);
+    writer.write_raw(ChunkPos::new(2, 0), &c).unwrap();
+    assert_eq!(sectors(&writer, (2, 0)), (4, 1));
+    writer.flush().unwrap();
+
+    assert_eq!(file_len(&path), 5 * 4096);
+    assert_contents(&path, &[((0, 0), &a), ((1, 0), &b), ((2, 0), &c)]);
+    common::cleanup(&dir);
+}
+
+#[test]
+fn flush_replaces_the_file_through_a_hidden_temp_file() {
+    let dir = common::test_dir("flush");
+    let region_dir = dir.join("region");
+    std::fs::create_dir_all(&region_dir).unwrap();
+    let world = World::new(dir.to_str().unwrap());
+    let a = raw(10, 1);
+
+    let mut writer = world.region_writer(RegionPos::new(-1, 2), Dimension::Overworld).unwrap();
+    writer.write_raw(ChunkPos::new(-32, 64), &a).unwrap();
+    // Nothing is written until the flush
+    assert_eq!(std::fs::read_dir(&region_dir).unwrap().count(), 0);
+    writer.flush().unwrap();
+
+    let names: Vec<_> = std::fs::read_dir(&region_dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names, vec!["r.-1.2.mca"]);
+    assert_eq!(file_len(&region_dir.join("r.-1.2.mca")), 3 * 4096);
+    assert_contents(&region_dir.join("r.-1.2.mca"), &[((0, 0), &a)]);
+
+    // A temp file left behind by a crash isn't taken for a region
+    std::fs::write(region_dir.join(".r.-1.2.tmp"), [0u8; 8192]).unwrap();
+    std::fs::write(region_dir.join("r.-1.2.mca.tmp"), [0u8; 8192]).unwrap();
+    let regions: Vec<_> = world.region_pos_iter(Dimension::Overworld).unwrap().collect();
+    assert_eq!(regions, vec![RegionPos::new(-1, 2)]);
+
+    // Flushing again over the leftover temp file still works
+    let b = raw(20, 2);
+    writer.write_raw(ChunkPos::new(-31, 64), &b).unwrap();
+    writer.flush().unwrap();
+    assert!(!region_dir.join(".r.-1.2.tmp").exists());
+    assert_contents(&region_dir.join("r.-1.2.mca"), &[((0, 0), &a), ((1, 0), &b)]);
+    common::cleanup(&dir);
+}
+
+/// A real chunk's payload, so the compacted region can be verified, padded with `padding` bytes of
+/// junk NBT can't reach
+fn chunk_payload(x: i32, z: i32, padding: usize) -> Vec<u8> {
+    let nbt = parse_snbt(&format!("{{Level:{{xPos:{x},zPos:{z},LastUpdate:5L}}}}")).unwrap();
+    let mut nbt = nbt.write(Vec::new(), "").unwrap();
+    nbt.resize(nbt.len() + padding, 0xaa);
+    common::payload(&nbt)
+}
+
+#[test]
+fn compaction_keeps_every_readable_chunk() {
+    let dir = common::test_dir("compact");
+    let path = dir.join("r.1.-1.mca");
+    // Out of order, with gaps, one with more sectors than it needs and the last one cut short
+    let chunks = [((0, 0), 5, 1, chunk_payload(32, -32, 0)), ((1, 0), 2, 3, chunk_payload(33, -32, 100)),
+                  ((0, 1), 9, 2, chunk_payload(32, -31, 5000))];
+    let mut data = vec![0u8; 8192];
+    for &((x, z), offset, sector_count, ref payload) in &chunks {
+        let index = (x + z * 32) as usize;
+        data[index * 4..index * 4 + 4].copy_from_slice(&[0, 0, offset, sector_count]);
+        data[4096 + index * 4..4096 + index * 4 + 4].copy_from_slice(&(100 + index as u32).to_be_bytes());
+        let start = offset as usize * 4096;
+        if data.len() < start + payload.len() {
+            data.resize(start + payload.len(), 0);
+        }
+        data[start..start + payload.len()].copy_from_slice(payload);
+    }
+    // Entirely past the end of the file
+    data[31 * 4..31 * 4 + 4].copy_from_slice(&[0, 0, 40, 1]);
+    data[4096 + 31 * 4..4096 + 31 * 4 + 4].copy_from_slice(&7u32.to_be_bytes());
+    std::fs::write(&path, &data).unwrap();
+
+    let mut writer = RegionWriter::open(&path).unwrap();
+    writer.set_pos(RegionPos::new(1, -1));
+    let (reclaimed, dropped) = writer.compact();
+    assert_eq!(dropped, vec![ChunkPos::new(63, -32)]);
+    writer.flush().unwrap();
+    // The flushed file is what later compactions are measured against
+    assert_eq!(writer.compact(), (0, Vec::new()));
+
+    // Sectors 2, 3 and 4..6 for the three chunks
+    assert_eq!(file_len(&path), 6 * 4096);
+    // Counted from the file as it was, which ended part way through a sector
+    assert_eq!(reclaimed, data.len() as u64 - 6 * 4096);
+    let expected: Vec<_> = chunks.iter().map(|((x, z), _, _, payload)| ((*x, *z), &payload[..])).collect();
+    assert_contents(&path, &expected);
+
+    let mut region = Region::open(File::open(&path).unwrap()).unwrap();
+    region.set_pos(RegionPos::new(1, -1));
+    assert_eq!(region.sector_range(ChunkPos::new(1, 0)), Some(2..3));
+    assert_eq!(region.sector_range(ChunkPos::new(0, 0)), Some(3..4));
+    assert_eq!(region.sector_range(ChunkPos::new(0, 1)), Some(4..6));
+    assert_eq!(region.timestamp(ChunkPos::new(0, 1)), Some(132));
+    assert_eq!(region.timestamp(ChunkPos::new(31, 0)), None);
+    assert!(region.verify().unwrap().is_empty());
+
+    // Already compact, so there's nothing more to do
+    let mut writer = RegionWriter::open(&path).unwrap();
+    assert_eq!(writer.compact(), (0, Vec::new()));
+    common::cleanup(&dir);
+}
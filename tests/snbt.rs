@@ -0,0 +1,139 @@
+use java_string::{JavaCodePoint, JavaString};
+use mc_utils::nbt::snbt::parse_snbt;
+use mc_utils::nbt::{NbtCompound, NbtErrorKind, NbtList, NbtTag};
+
+fn round_trip(compound: &NbtCompound) -> NbtCompound {
+    let printed = compound.to_string();
+    parse_snbt(&printed).unwrap_or_else(|err| panic!("{printed}: {err}"))
+}
+
+fn lone_surrogate() -> JavaString {
+    let mut string = JavaString::from("a");
+    string.push_java(JavaCodePoint::from_u32(0xD800).unwrap());
+    string.push('b');
+    string
+}
+
+#[test]
+fn numbers_keep_their_types() {
+    let parsed = parse_snbt("{a:1b,b:-2s,c:3,d:4L,e:1.5f,f:2.5d,g:2.5,h:true,i:false,j:1e3,k:12abc}").unwrap();
+    assert_eq!(parsed["a"], NbtTag::Byte(1));
+    assert_eq!(parsed["b"], NbtTag::Short(-2));
+    assert_eq!(parsed["c"], NbtTag::Int(3));
+    assert_eq!(parsed["d"], NbtTag::Long(4));
+    assert_eq!(parsed["e"], NbtTag::Float(1.5));
+    assert_eq!(parsed["f"], NbtTag::Double(2.5));
+    assert_eq!(parsed["g"], NbtTag::Double(2.5));
+    assert_eq!(parsed["h"], NbtTag::Byte(1));
+    assert_eq!(parsed["i"], NbtTag::Byte(0));
+    assert_eq!(parsed["j"], NbtTag::Double(1000.0));
+    // Doesn't look like a number, so it's an unquoted string
+    assert_eq!(parsed["k"], NbtTag::String("12abc".into()));
+
+    // Out of range for the suffix falls back to a string too
+    assert_eq!(parse_snbt("{a:300b}").unwrap()["a"], NbtTag::String("300b".into()));
+
+    let mut compound = NbtCompound::new();
+    compound.insert("byte", i8::MIN);
+    compound.insert("short", i16::MAX);
+    compound.insert("int", i32::MIN);
+    compound.insert("long", i64::MAX);
+    compound.insert("float", 0.1f32);
+    compound.insert("double", -1.0e-5f64);
+    assert_eq!(compound.to_string(), "{byte:-128b,short:32767s,int:-2147483648,long:9223372036854775807L,float:0.1f,double:-0.00001d}");
+    assert_eq!(round_trip(&compound), compound);
+}
+
+#[test]
+fn infinity_and_nan() {
+    let mut compound = NbtCompound::new();
+    compound.insert("inf", f32::INFINITY);
+    compound.insert("neg", f64::NEG_INFINITY);
+    compound.insert("nan", f32::NAN);
+    let printed = compound.to_string();
+    assert_eq!(printed, "{inf:Infinityf,neg:-Infinityd,nan:NaNf}");
+
+    let parsed = parse_snbt(&printed).unwrap();
+    assert_eq!(parsed["inf"], NbtTag::Float(f32::INFINITY));
+    assert_eq!(parsed["neg"], NbtTag::Double(f64::NEG_INFINITY));
+    assert!(parsed["nan"].as_float().unwrap().is_nan());
+    assert_eq!(parsed.to_string(), printed);
+}
+
+#[test]
+fn typed_arrays() {
+    let parsed = parse_snbt("{b:[B;1b,-2b],i:[I;1,2s,3b],l:[L;1L,2,3s],e:[I;]}").unwrap();
+    assert_eq!(parsed["b"], NbtTag::ByteArray(vec![1, -2]));
+    // Smaller integers widen into the array type
+    assert_eq!(parsed["i"], NbtTag::IntArray(vec![1, 2, 3]));
+    assert_eq!(parsed["l"], NbtTag::LongArray(vec![1, 2, 3]));
+    assert_eq!(parsed["e"], NbtTag::IntArray(vec![]));
+    assert_eq!(parsed.to_string(), "{b:[B;1b,-2b],i:[I;1,2,3],l:[L;1L,2L,3L],e:[I;]}");
+    assert_eq!(round_trip(&parsed), parsed);
+
+    let mut list = NbtList::new();
+    list.push(NbtList::new()).unwrap();
+    let mut compound = NbtCompound::new();
+    compound.insert("nested", list);
+    compound.insert("empty", NbtCompound::new());
+    assert_eq!(compound.to_string(), "{nested:[[]],empty:{}}");
+    assert_eq!(round_trip(&compound), compound);
+}
+
+#[test]
+fn quoted_and_escaped_keys() {
+    let parsed = parse_snbt(r#"{"a b":1,'it\'s':2,"q\"k":3,"":4,plain_key.x-y+z:5,'"':6}"#).unwrap();
+    let keys: Vec<_> = parsed.iter().map(|(key, _)| key.to_string()).collect();
+    assert_eq!(keys, vec!["a b", "it's", "q\"k", "", "plain_key.x-y+z", "\""]);
+    assert_eq!(parsed.to_string(), r#"{"a b":1,"it's":2,"q\"k":3,"":4,plain_key.x-y+z:5,"\"":6}"#);
+    assert_eq!(round_trip(&parsed), parsed);
+
+    let mut compound = NbtCompound::new();
+    compound.insert("line\nbreak", "tab\there \\ \"quoted\" \u{1}");
+    compound.insert(lone_surrogate(), lone_surrogate());
+    assert_eq!(compound.to_string(), r#"{"line\nbreak":"tab\there \\ \"quoted\" \u0001","a\uD800b":"a\uD800b"}"#);
+    assert_eq!(round_trip(&compound), compound);
+}
+
+#[test]
+fn unicode_escapes() {
+    let parsed = parse_snbt(r#"{pair:"😀",lone:"a\uD800b",low:"\uDC00\uD800",bmp:"é"}"#).unwrap();
+    assert_eq!(parsed["pair"].as_string().unwrap(), "\u{1f600}");
+    assert_eq!(parsed["lone"].as_string().unwrap(), lone_surrogate().as_java_str());
+    assert_eq!(parsed["bmp"].as_string().unwrap(), "\u{e9}");
+
+    let low: Vec<u32> = parsed["low"].as_string().unwrap().chars().map(|c| c.as_u32()).collect();
+    assert_eq!(low, vec![0xDC00, 0xD800]);
+    assert_eq!(round_trip(&parsed), parsed);
+}
+
+#[test]
+fn malformed_input_errors() {
+    let inputs = [
+        "",
+        "   ",
+        "[1,2]",
+        "1",
+        "{",
+        "{a}",
+        "{a:}",
+        "{a:1,}",
+        "{a:1 b:2}",
+        "{:1}",
+        "{a:1}}",
+        "{a:1} trailing",
+        "{a:[1,2b]}",
+        "{a:[1,}",
+        "{a:[B;1L]}",
+        "{a:[I;1.5]}",
+        "{a:[B;1b}",
+        r#"{a:"unterminated}"#,
+        r#"{a:"\q"}"#,
+        r#"{a:"\uZZZZ"}"#,
+        r#"{a:"\u12"}"#,
+    ];
+    for input in inputs {
+        let err = parse_snbt(input).expect_err(input);
+        assert!(matches!(err.kind(), NbtErrorKind::InvalidSnbt { .. }), "{input}: {err}");
+    }
+}
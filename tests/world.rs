@@ -0,0 +1,92 @@
+mod common;
+
+use std::path::Path;
+use mc_utils::positions::{ChunkPos, RegionPos};
+use mc_utils::region::RegionWriter;
+use mc_utils::world::{Dimension, World};
+
+/// Writes a region file into the overworld of the world at `dir`, with a chunk for each SNBT string
+fn write_region(dir: &Path, pos: RegionPos, chunks: &[(ChunkPos, String)]) {
+    std::fs::create_dir_all(dir.join("region")).unwrap();
+    let mut writer = RegionWriter::open(dir.join(format!("region/r.{}.{}.mca", pos.x, pos.z))).unwrap();
+    writer.set_pos(pos);
+    for (chunk_pos, snbt) in chunks {
+        writer.write_raw(*chunk_pos, &common::snbt_payload(snbt)).unwrap();
+    }
+    writer.flush().unwrap();
+}
+
+#[test]
+fn scheduled_ticks_run_in_game_time_order() {
+    let dir = common::test_dir("world_ticks");
+    // Saved at different times, so the delays count from different ticks
+    write_region(&dir, RegionPos::new(0, 0), &[
+        (ChunkPos::new(0, 0), "{Level:{xPos:0,zPos:0,LastUpdate:100L,TileTicks:[\
+            {i:\"a\",x:0,y:0,z:0,t:5,p:0},{i:\"b\",x:1,y:0,z:0,t:1,p:0},{i:\"c\",x:2,y:0,z:0,t:5,p:0}]}}".to_string()),
+        (ChunkPos::new(1, 0), "{Level:{xPos:1,zPos:0,LastUpdate:90L,TileTicks:[\
+            {i:\"d\",x:16,y:0,z:0,t:15,p:-1},{i:\"e\",x:17,y:0,z:0,t:12,p:1},{i:\"f\",x:18,y:0,z:0,t:20,p:0}]}}".to_string())
+    ]);
+
+    let mut world = World::new(dir.to_str().unwrap());
+    let chunks = [ChunkPos::new(0, 0), ChunkPos::new(1, 0), ChunkPos::new(5, 5)];
+    let ticks = world.scheduled_ticks(chunks, Dimension::Overworld).unwrap();
+    let order: Vec<_> = ticks.iter().map(|tick| tick.block.as_str()).collect();
+    // b at 101, e at 102, then at 105 d with the lowest priority before a and c in the order they
+    // were saved, and f at 110
+    assert_eq!(order, vec!["b", "e", "d", "a", "c", "f"]);
+    // The ticks themselves aren't changed, the delays are still relative to their own chunk
+    assert_eq!(ticks[1].delay, 12);
+
+    common::cleanup(&dir);
+}
+
+fn last_update(world: &mut World, region_x: i32) -> i64 {
+    world.get_chunk(ChunkPos::new(region_x * 32, 0), Dimension::Overworld).unwrap().unwrap().last_update
+}
+
+/// Replaces region (`x`, 0), whose one chunk has `LastUpdate` `last_update`
+fn write_numbered_region(dir: &Path, x: i32, last_update: i64) {
+    write_region(dir, RegionPos::new(x, 0), &[(ChunkPos::new(x * 32, 0), format!("{{Level:{{xPos:{},zPos:0,LastUpdate:{last_update}L}}}}", x * 32))]);
+}
+
+#[test]
+fn least_recently_used_region_is_closed_first() {
+    let dir = common::test_dir("world_regions");
+    for x in 0..65 {
+        write_numbered_region(&dir, x, 1);
+    }
+
+    // The 64 regions the world keeps open by default, with region 0 then used again
+    let mut world = World::new(dir.to_str().unwrap());
+    for x in 0..64 {
+        assert_eq!(last_update(&mut world, x), 1);
+    }
+    last_update(&mut world, 0);
+    // Opening one more closes region 1, the least recently used
+    last_update(&mut world, 64);
+
+    // Regions still open answer from what they'd already read, a closed one is opened again and
+    // sees the new file
+    for x in [0, 1, 2, 64] {
+        write_numbered_region(&dir, x, 2);
+    }
+    assert_eq!(last_update(&mut world, 0), 1);
+    assert_eq!(last_update(&mut world, 2), 1);
+    assert_eq!(last_update(&mut world, 64), 1);
+    // Which in turn closes region 3
+    assert_eq!(last_update(&mut world, 1), 2);
+    write_numbered_region(&dir, 3, 2);
+    assert_eq!(last_update(&mut world, 3), 2);
+
+    // Lowering the limit closes the least recently used straight away, leaving 1 and 3
+    world.set_max_open_regions(2);
+    for x in [0, 1, 3, 64] {
+        write_numbered_region(&dir, x, 3);
+    }
+    assert_eq!(last_update(&mut world, 1), 2);
+    assert_eq!(last_update(&mut world, 3), 2);
+    assert_eq!(last_update(&mut world, 0), 3);
+    assert_eq!(last_update(&mut world, 64), 3);
+
+    common::cleanup(&dir);
+}